@@ -1,4 +1,4 @@
-use std::{env, fs, fs::File, io::Write, path::PathBuf};
+use std::{fs, fs::File, io::Write, path::PathBuf};
 
 use clap::Parser;
 use serde::{Deserialize, Serialize};
@@ -15,7 +15,7 @@ fn main() {
     let mut out = File::create(options.bincode_path).expect("failed to open file for write");
     for group in data {
         for (i, sound) in group.sounds.into_iter().enumerate() {
-            out.write(
+            out.write_all(
                 &bincode::serialize(&BincodeMetadata {
                     guild: options.guild,
                     id: sound.id,
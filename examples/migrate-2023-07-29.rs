@@ -3,7 +3,7 @@ use std::{fs, path::PathBuf};
 use bincode::Options as _;
 use clap::Parser;
 use serde::{Deserialize, Serialize};
-use serenity::model::application::component::ButtonStyle;
+use serenity::all::ButtonStyle;
 use ulid::Ulid;
 
 #[derive(Deserialize)]
@@ -61,11 +61,7 @@ fn main() {
             .allow_trailing_bytes(),
     );
     let mut sounds = Vec::new();
-    loop {
-        let metadata = match SoundMetadataOld::deserialize(&mut deserializer) {
-            Ok(metadata) => metadata,
-            Err(_) => break,
-        };
+    while let Ok(metadata) = SoundMetadataOld::deserialize(&mut deserializer) {
         sounds.push(metadata);
     }
 
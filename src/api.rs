@@ -1,21 +1,65 @@
-use std::sync::Arc;
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    io::{Cursor, Write},
+    path::{Path as FsPath, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
 
 use axum::{
-    Router,
-    extract::{FromRef, Path, State},
-    http::StatusCode,
+    Json, Router,
+    extract::{
+        DefaultBodyLimit, FromRef, FromRequest, FromRequestParts, MatchedPath, Multipart, Path,
+        Query, Request, State,
+        multipart::MultipartError,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{
+        Html, IntoResponse, Response,
+        sse::{Event as SseEvent, KeepAlive, Sse},
+    },
     routing,
 };
+use futures::{Stream, stream};
+use log::warn;
 use rand::seq::IteratorRandom;
-use serenity::all::{Cache, ChannelId, GuildId, Http, UserId};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serenity::all::{ButtonStyle, Cache, ChannelId, GuildId, Http, UserId};
 use songbird::{CoreEvent, Event, Songbird};
-use tokio::sync::Mutex;
+use thiserror::Error as ThisError;
+use tokio::{
+    io::AsyncWriteExt,
+    process::Command,
+    sync::{Mutex, broadcast::error::RecvError, oneshot},
+};
 use ulid::Ulid;
+use zip::{ZipWriter, write::FileOptions as ZipFileOptions};
 
 use crate::{
-    VoiceHandler, find_voice_channel, history::History, recorder::Recorder, soundboard::Soundboard,
+    MAX_FILE_SIZE, PlayError, VoiceHandler,
+    auth::{self, ApiTokens, Scope},
+    button, command,
+    events::{Events, GuildEvent, PlaySource},
+    find_voice_channel,
+    history::History,
+    metrics::{self, Metrics, MetricsWriter},
+    playing::NowPlaying,
+    recorder::{FREQUENCY, Recorder, RecorderAction},
+    soundboard::{SoundDetails, SoundUpdate, Soundboard, SoundboardError},
+    store::GroupMetadata,
+    wav,
 };
 
+const DEFAULT_RECORDING_CHUNKS: usize = 10;
+const DEFAULT_CHUNK_MIN_DURATION: Duration = Duration::from_millis(500);
+/// How long a metrics scrape waits for the stats of each guild recorder.
+const RECORDER_STATS_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[derive(FromRef, Clone)]
 pub struct ApiState {
     pub http: Arc<Http>,
@@ -24,13 +68,553 @@ pub struct ApiState {
     pub recorder: Arc<Mutex<Recorder>>,
     pub soundboard: Arc<Soundboard>,
     pub history: Arc<History>,
+    pub now_playing: Arc<NowPlaying>,
+    pub events: Arc<Events>,
+    pub metrics: Arc<Metrics>,
+    pub settings: ApiSettings,
+}
+
+/// Options of the bot that the API honors like the Discord commands do.
+#[derive(Clone)]
+pub struct ApiSettings {
+    pub allow_delete: bool,
+    pub allow_grey: bool,
+    pub ffmpeg_path: PathBuf,
+}
+
+/// Errors answered as `{"code": "sound_not_found", "error": "Cannot find that sound."}`.
+#[derive(ThisError, Debug)]
+pub enum ApiError {
+    #[error(transparent)]
+    Soundboard(#[from] SoundboardError),
+    #[error(transparent)]
+    Play(#[from] PlayError),
+    #[error("The user is not in a voice channel.")]
+    UserNotInVoice,
+    #[error("Failed to join the voice channel.")]
+    VoiceJoinFailed,
+    #[error("Failed to leave the voice channel.")]
+    VoiceLeaveFailed,
+    #[error("Sounds deletion is disabled.")]
+    DeleteDisabled,
+    #[error("The user is not in the recorder's whitelist.")]
+    NotWhitelisted,
+    #[error("No voice data found for this user.")]
+    NoVoiceData,
+    #[error("Failed to encode the recording.")]
+    EncodingFailed,
+    #[error("The recorder of this guild is not available.")]
+    RecorderUnavailable,
+    #[error("Missing or unknown API token.")]
+    Unauthorized,
+    #[error("This API token is not allowed to do that.")]
+    Forbidden,
+    #[error("{0}")]
+    InvalidRequest(String),
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::Soundboard(err) => match err {
+                SoundboardError::NameTaken => "name_taken",
+                SoundboardError::GroupNameTaken => "group_name_taken",
+                SoundboardError::TooLong => "sound_too_long",
+                SoundboardError::SoundFetch => "sound_fetch_failed",
+                SoundboardError::InvalidSound => "invalid_sound",
+                SoundboardError::TranscodingFailed => "transcoding_failed",
+                SoundboardError::SoundWrite => "sound_write_failed",
+                SoundboardError::SoundNotFound => "sound_not_found",
+                SoundboardError::GroupNotFound => "group_not_found",
+                SoundboardError::SoundNameAmbiguous => "sound_name_ambiguous",
+                SoundboardError::DeleteFailed => "delete_failed",
+                SoundboardError::BackupFailed => "backup_failed",
+                SoundboardError::DuplicateSound(_) => "duplicate_sound",
+                SoundboardError::InvalidArchive => "invalid_archive",
+                SoundboardError::MissingGroup => "missing_group",
+                SoundboardError::EmptyGroupName => "empty_group_name",
+            },
+            ApiError::Play(err) => match err {
+                PlayError::SoundNotFound => "sound_not_found",
+                PlayError::NotInVoice => "not_in_voice",
+            },
+            ApiError::UserNotInVoice => "user_not_in_voice",
+            ApiError::VoiceJoinFailed => "voice_join_failed",
+            ApiError::VoiceLeaveFailed => "voice_leave_failed",
+            ApiError::DeleteDisabled => "delete_disabled",
+            ApiError::NotWhitelisted => "not_whitelisted",
+            ApiError::NoVoiceData => "no_voice_data",
+            ApiError::EncodingFailed => "encoding_failed",
+            ApiError::RecorderUnavailable => "recorder_unavailable",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden => "forbidden",
+            ApiError::InvalidRequest(_) => "invalid_request",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Soundboard(err) => match err {
+                SoundboardError::NameTaken
+                | SoundboardError::GroupNameTaken
+                | SoundboardError::DuplicateSound(_) => StatusCode::CONFLICT,
+                SoundboardError::TooLong => StatusCode::PAYLOAD_TOO_LARGE,
+                SoundboardError::InvalidSound
+                | SoundboardError::TranscodingFailed
+                | SoundboardError::InvalidArchive => StatusCode::UNPROCESSABLE_ENTITY,
+                SoundboardError::SoundNotFound | SoundboardError::GroupNotFound => {
+                    StatusCode::NOT_FOUND
+                }
+                SoundboardError::SoundNameAmbiguous
+                | SoundboardError::MissingGroup
+                | SoundboardError::EmptyGroupName => StatusCode::BAD_REQUEST,
+                SoundboardError::SoundFetch
+                | SoundboardError::SoundWrite
+                | SoundboardError::DeleteFailed
+                | SoundboardError::BackupFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::Play(err) => match err {
+                PlayError::SoundNotFound => StatusCode::NOT_FOUND,
+                PlayError::NotInVoice => StatusCode::CONFLICT,
+            },
+            ApiError::UserNotInVoice => StatusCode::NOT_FOUND,
+            ApiError::VoiceJoinFailed | ApiError::VoiceLeaveFailed => StatusCode::BAD_GATEWAY,
+            ApiError::DeleteDisabled | ApiError::NotWhitelisted | ApiError::Forbidden => {
+                StatusCode::FORBIDDEN
+            }
+            ApiError::NoVoiceData => StatusCode::NOT_FOUND,
+            ApiError::EncodingFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::RecorderUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (
+            self.status(),
+            Json(json!({
+                "code": self.code(),
+                "error": self.to_string(),
+            })),
+        )
+            .into_response()
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl From<MultipartError> for ApiError {
+    fn from(err: MultipartError) -> Self {
+        ApiError::InvalidRequest(err.body_text())
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+// Extractors answering with an `ApiError` when the request is invalid.
+
+#[derive(FromRequestParts)]
+#[from_request(via(Path), rejection(ApiError))]
+struct ApiPath<T>(T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(Query), rejection(ApiError))]
+struct ApiQuery<T>(T);
+
+#[derive(FromRequest)]
+#[from_request(via(Json), rejection(ApiError))]
+struct ApiJson<T>(T);
+
+#[derive(Serialize)]
+struct GroupJson {
+    group: String,
+    sounds: Vec<SoundJson>,
+}
+
+#[derive(Serialize)]
+struct SoundJson {
+    id: Ulid,
+    name: String,
+    emoji: Option<String>,
+    color: &'static str,
+    group: String,
+    index: usize,
+    /// In seconds.
+    duration: f64,
+}
+
+impl From<SoundDetails> for SoundJson {
+    fn from(sound: SoundDetails) -> Self {
+        Self {
+            id: sound.metadata.id,
+            name: sound.metadata.name,
+            emoji: sound.metadata.emoji,
+            color: button::as_str(sound.metadata.color),
+            group: sound.metadata.group,
+            index: sound.metadata.index,
+            duration: sound.duration.as_secs_f64(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GroupSettingsJson {
+    name: String,
+    /// Default color of the sounds uploaded to the group without one.
+    color: Option<&'static str>,
+    emoji: Option<String>,
+    description: Option<String>,
+}
+
+impl From<GroupMetadata> for GroupSettingsJson {
+    fn from(group: GroupMetadata) -> Self {
+        Self {
+            name: group.name,
+            color: group.color.map(button::as_str),
+            emoji: group.emoji,
+            description: group.description,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    name: Option<String>,
+    group: Option<String>,
+}
+
+async fn list_sounds(
+    State(soundboard): State<Arc<Soundboard>>,
+    ApiPath(guild): ApiPath<GuildId>,
+    ApiQuery(search): ApiQuery<SearchQuery>,
+) -> Json<Vec<GroupJson>> {
+    Json(
+        soundboard
+            .search(guild, search.name.as_deref(), search.group.as_deref())
+            .await
+            .into_iter()
+            .map(|(group, sounds)| GroupJson {
+                group,
+                sounds: sounds.into_iter().map(SoundJson::from).collect(),
+            })
+            .collect(),
+    )
+}
+
+async fn get_sound(
+    State(soundboard): State<Arc<Soundboard>>,
+    ApiPath((guild, sound)): ApiPath<(GuildId, Ulid)>,
+) -> ApiResult<Json<SoundJson>> {
+    soundboard
+        .details(guild, sound)
+        .await
+        .map(|sound| Json(sound.into()))
+        .ok_or(ApiError::Soundboard(SoundboardError::SoundNotFound))
+}
+
+#[derive(Serialize)]
+struct UploadedJson {
+    #[serde(flatten)]
+    sound: SoundJson,
+    /// Name of an identical sound already in the guild.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PlayedJson {
+    id: Ulid,
+}
+
+#[derive(Serialize)]
+struct JoinedJson {
+    channel: ChannelId,
+}
+
+#[derive(Serialize)]
+struct VoiceJson {
+    channel: Option<ChannelId>,
+    users: Vec<VoiceUserJson>,
+    /// Whether the voice of at least one connected user is being recorded.
+    recording: bool,
+    playing: Vec<PlayingJson>,
+}
+
+#[derive(Serialize)]
+struct VoiceUserJson {
+    id: UserId,
+    name: String,
+    recorded: bool,
+}
+
+#[derive(Serialize)]
+struct PlayingJson {
+    id: Ulid,
+    name: Option<String>,
+    /// Seconds.
+    position: f64,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum AudioFormat {
+    #[default]
+    Wav,
+    /// Opus in an Ogg container, transcoded by ffmpeg.
+    Ogg,
+}
+
+impl AudioFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Ogg => "ogg",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "audio/wav",
+            AudioFormat::Ogg => "audio/ogg",
+        }
+    }
+}
+
+/// Window of the voice buffer to download, the last `duration` before
+/// `offset` from the end, the whole buffer by default.
+#[derive(Deserialize)]
+struct RecordingQuery {
+    duration: Option<String>,
+    offset: Option<String>,
+    #[serde(default)]
+    format: AudioFormat,
+}
+
+#[derive(Deserialize)]
+struct RecordingChunksQuery {
+    count: Option<usize>,
+    min_duration: Option<String>,
+    #[serde(default)]
+    format: AudioFormat,
+}
+
+fn parse_duration(duration: Option<&str>) -> ApiResult<Option<Duration>> {
+    duration
+        .map(|duration| {
+            parse_duration::parse(duration)
+                .map_err(|_| ApiError::InvalidRequest("Invalid duration.".to_owned()))
+        })
+        .transpose()
+}
+
+fn parse_color(color: &str) -> ApiResult<ButtonStyle> {
+    button::try_parse_color(color).ok_or_else(|| {
+        ApiError::InvalidRequest("Invalid color, use blue, green, red or grey.".to_owned())
+    })
+}
+
+/// Reject sound and group names left empty or blank.
+fn check_names<'a>(names: impl IntoIterator<Item = &'a String>) -> ApiResult<()> {
+    if names.into_iter().any(|name| name.trim().is_empty()) {
+        return Err(ApiError::InvalidRequest(
+            "Name and group cannot be empty.".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+fn parse_emoji(emoji: &str) -> ApiResult<String> {
+    command::parse_emoji(emoji).ok_or_else(|| ApiError::InvalidRequest("Invalid emoji.".to_owned()))
+}
+
+async fn download_sound(
+    State(soundboard): State<Arc<Soundboard>>,
+    ApiPath((guild, sound)): ApiPath<(GuildId, Ulid)>,
+) -> ApiResult<impl IntoResponse> {
+    let data = soundboard.get_wav(guild, sound).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "audio/wav".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{sound}.wav\""),
+            ),
+        ],
+        data,
+    ))
+}
+
+/// Multipart upload with a `sound` file and `name`, `group`, and optional
+/// `emoji`, `color` and `index` fields.
+async fn upload_sound(
+    State(soundboard): State<Arc<Soundboard>>,
+    State(settings): State<ApiSettings>,
+    ApiPath(guild): ApiPath<GuildId>,
+    mut multipart: Multipart,
+) -> ApiResult<(StatusCode, Json<UploadedJson>)> {
+    let (mut sound, mut name, mut group, mut emoji, mut color, mut index) =
+        (None, None, None, None, None, None);
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("sound") => {
+                let filename = field.file_name().unwrap_or_default().to_owned();
+                sound = Some((filename, field.bytes().await?.to_vec()));
+            }
+            Some("name") => name = Some(field.text().await?),
+            Some("group") => group = Some(field.text().await?),
+            Some("emoji") => emoji = Some(field.text().await?),
+            Some("color") => color = Some(field.text().await?),
+            Some("index") => index = Some(field.text().await?),
+            _ => {}
+        }
+    }
+    let (Some((filename, data)), Some(name), Some(group)) = (sound, name, group) else {
+        return Err(ApiError::InvalidRequest(
+            "Missing sound, name or group field.".to_owned(),
+        ));
+    };
+    check_names([&name, &group])?;
+    let index = match index {
+        Some(index) => Some(
+            index
+                .parse::<usize>()
+                .map_err(|_| ApiError::InvalidRequest("Invalid index field.".to_owned()))?,
+        ),
+        None => None,
+    };
+    let emoji = emoji
+        .as_deref()
+        .filter(|emoji| !emoji.is_empty())
+        .map(parse_emoji)
+        .transpose()?;
+    let color = match color {
+        Some(color) => parse_color(&color)?,
+        None => {
+            soundboard
+                .default_color(guild, &group, &name, settings.allow_grey)
+                .await
+        }
+    };
+
+    let (id, duplicate) = soundboard
+        .add(data, &filename, guild, name, emoji, color, group, index)
+        .await?;
+    let sound = soundboard
+        .details(guild, id)
+        .await
+        .ok_or(ApiError::Soundboard(SoundboardError::SoundNotFound))?;
+    Ok((
+        StatusCode::CREATED,
+        Json(UploadedJson {
+            sound: sound.into(),
+            duplicate,
+        }),
+    ))
+}
+
+/// Fields left out are kept, an empty emoji removes it.
+#[derive(Deserialize)]
+struct SoundPatch {
+    name: Option<String>,
+    group: Option<String>,
+    emoji: Option<String>,
+    color: Option<String>,
+    index: Option<usize>,
+}
+
+async fn update_sound(
+    State(soundboard): State<Arc<Soundboard>>,
+    ApiPath((guild, sound)): ApiPath<(GuildId, Ulid)>,
+    ApiJson(patch): ApiJson<SoundPatch>,
+) -> ApiResult<Json<SoundJson>> {
+    check_names(patch.name.iter().chain(&patch.group))?;
+    let update = SoundUpdate {
+        name: patch.name,
+        group: patch.group,
+        emoji: match patch.emoji.as_deref() {
+            None => None,
+            Some("") => Some(None),
+            Some(emoji) => Some(Some(parse_emoji(emoji)?)),
+        },
+        color: patch.color.as_deref().map(parse_color).transpose()?,
+        position: patch.index,
+    };
+    let (sound, _changed) = soundboard.update(guild, sound, update).await?;
+    Ok(Json(sound.into()))
+}
+
+async fn delete_sound(
+    State(soundboard): State<Arc<Soundboard>>,
+    State(settings): State<ApiSettings>,
+    ApiPath((guild, sound)): ApiPath<(GuildId, Ulid)>,
+) -> ApiResult<StatusCode> {
+    if !settings.allow_delete {
+        return Err(ApiError::DeleteDisabled);
+    }
+    soundboard.delete_id(guild, sound).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_groups(
+    State(soundboard): State<Arc<Soundboard>>,
+    ApiPath(guild): ApiPath<GuildId>,
+) -> Json<Vec<GroupSettingsJson>> {
+    Json(
+        soundboard
+            .groups(guild)
+            .await
+            .into_iter()
+            .map(GroupSettingsJson::from)
+            .collect(),
+    )
 }
 
 async fn join_channel(
     State(songbird): State<Arc<Songbird>>,
     State(recorder): State<Arc<Mutex<Recorder>>>,
-    Path((guild, channel)): Path<(GuildId, ChannelId)>,
-) -> StatusCode {
+    ApiPath((guild, channel)): ApiPath<(GuildId, ChannelId)>,
+) -> ApiResult<Json<JoinedJson>> {
+    join(songbird, recorder, guild, channel).await
+}
+
+async fn join_user_channel(
+    State(http): State<Arc<Http>>,
+    State(cache): State<Arc<Cache>>,
+    State(songbird): State<Arc<Songbird>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    ApiPath((guild, user)): ApiPath<(GuildId, UserId)>,
+) -> ApiResult<Json<JoinedJson>> {
+    let channel = find_voice_channel(http, cache, guild, user)
+        .await
+        .ok_or(ApiError::UserNotInVoice)?;
+    join(songbird, recorder, guild, channel).await
+}
+
+async fn join(
+    songbird: Arc<Songbird>,
+    recorder: Arc<Mutex<Recorder>>,
+    guild: GuildId,
+    channel: ChannelId,
+) -> ApiResult<Json<JoinedJson>> {
     let call = songbird.get_or_insert(guild);
     let mut call_lock = call.lock().await;
 
@@ -44,108 +628,571 @@ async fn join_channel(
     );
     call_lock.add_global_event(Event::Core(CoreEvent::VoiceTick), recorder);
 
-    let handle = call_lock
-        .join(channel)
-        .await
-        .expect("Voice connexion failure");
-    drop(call_lock);
-    handle.await.expect("Voice connexion failure");
+    let joined = match call_lock.join(channel).await {
+        Ok(handle) => {
+            drop(call_lock);
+            handle.await
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = joined {
+        warn!("failed to join voice channel {channel} of guild {guild}: {err}");
+        return Err(ApiError::VoiceJoinFailed);
+    }
 
-    StatusCode::OK
+    Ok(Json(JoinedJson { channel }))
 }
 
-async fn join_user_channel(
-    State(http): State<Arc<Http>>,
+async fn leave_channel(
+    State(songbird): State<Arc<Songbird>>,
+    ApiPath(guild): ApiPath<GuildId>,
+) -> ApiResult<StatusCode> {
+    let call = songbird.get(guild).ok_or(PlayError::NotInVoice)?;
+    let mut call_lock = call.lock().await;
+    if call_lock.current_channel().is_none() {
+        return Err(PlayError::NotInVoice.into());
+    }
+    call_lock.stop();
+    if let Err(err) = call_lock.leave().await {
+        warn!("failed to leave voice channel of guild {guild}: {err}");
+        return Err(ApiError::VoiceLeaveFailed);
+    }
+    call_lock.remove_all_global_events();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn voice_status(
     State(cache): State<Arc<Cache>>,
     State(songbird): State<Arc<Songbird>>,
     State(recorder): State<Arc<Mutex<Recorder>>>,
-    Path((guild, user)): Path<(GuildId, UserId)>,
-) -> StatusCode {
-    let Some(channel) = find_voice_channel(http, cache, guild, user).await else {
-        return StatusCode::NOT_FOUND;
+    State(soundboard): State<Arc<Soundboard>>,
+    State(now_playing): State<Arc<NowPlaying>>,
+    ApiPath(guild): ApiPath<GuildId>,
+) -> Json<VoiceJson> {
+    let channel = match songbird.get(guild) {
+        Some(call) => call
+            .lock()
+            .await
+            .current_channel()
+            .map(|channel| ChannelId::new(channel.0.get())),
+        None => None,
+    };
+    let Some(channel) = channel else {
+        return Json(VoiceJson {
+            channel: None,
+            users: Vec::new(),
+            recording: false,
+            playing: Vec::new(),
+        });
+    };
+
+    let members = {
+        let bot = cache.current_user().id;
+        cache
+            .guild(guild)
+            .map(|guild| {
+                guild
+                    .voice_states
+                    .values()
+                    .filter(|state| state.channel_id == Some(channel) && state.user_id != bot)
+                    .map(|state| {
+                        let name = guild.members.get(&state.user_id).map_or_else(
+                            || state.user_id.to_string(),
+                            |member| member.display_name().to_owned(),
+                        );
+                        (state.user_id, name)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
     };
-    join_channel(State(songbird), State(recorder), Path((guild, channel))).await
+    let users = {
+        let recorder = recorder.lock().await;
+        members
+            .into_iter()
+            .map(|(id, name)| VoiceUserJson {
+                id,
+                name,
+                recorded: recorder.is_whitelisted(id),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut playing = Vec::new();
+    for (sound, position) in now_playing.get(guild).await {
+        playing.push(PlayingJson {
+            id: sound,
+            name: soundboard
+                .details(guild, sound)
+                .await
+                .map(|details| details.metadata.name),
+            position: position.as_secs_f64(),
+        });
+    }
+
+    Json(VoiceJson {
+        channel: Some(channel),
+        recording: users.iter().any(|user| user.recorded),
+        users,
+        playing,
+    })
 }
 
+/// Play a random sound among `a|b|c`.
 async fn play_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
-    Path((guild, sounds)): Path<(GuildId, String)>,
-) -> StatusCode {
-    let Some(selected) = sounds
+    State(now_playing): State<Arc<NowPlaying>>,
+    State(events): State<Arc<Events>>,
+    ApiPath((guild, sounds)): ApiPath<(GuildId, String)>,
+) -> ApiResult<Json<PlayedJson>> {
+    let selected = sounds
         .split('|')
         .choose(&mut rand::rng())
         .and_then(|s| s.parse().ok())
-    else {
-        return StatusCode::BAD_REQUEST;
-    };
-    if super::play_sound(songbird, &soundboard, guild, selected).await {
-        StatusCode::OK
-    } else {
-        StatusCode::NOT_FOUND
-    }
+        .ok_or_else(|| ApiError::InvalidRequest("Invalid sound id.".to_owned()))?;
+    play(
+        songbird,
+        &soundboard,
+        &now_playing,
+        &events,
+        guild,
+        selected,
+    )
+    .await
 }
 
 async fn play_random_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
-    Path(guild): Path<GuildId>,
-) -> StatusCode {
-    let Some(sound) = soundboard.random_id(guild).await else {
-        return StatusCode::NOT_FOUND;
-    };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    State(now_playing): State<Arc<NowPlaying>>,
+    State(events): State<Arc<Events>>,
+    ApiPath(guild): ApiPath<GuildId>,
+) -> ApiResult<Json<PlayedJson>> {
+    let sound = soundboard
+        .random_id(guild)
+        .await
+        .ok_or(PlayError::SoundNotFound)?;
+    play(songbird, &soundboard, &now_playing, &events, guild, sound).await
 }
 
 async fn play_latest_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
-    Path(guild): Path<GuildId>,
-) -> StatusCode {
-    let Some(sound) = soundboard.latest_id(guild).await else {
-        return StatusCode::NOT_FOUND;
-    };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    State(now_playing): State<Arc<NowPlaying>>,
+    State(events): State<Arc<Events>>,
+    ApiPath(guild): ApiPath<GuildId>,
+) -> ApiResult<Json<PlayedJson>> {
+    let sound = soundboard
+        .latest_id(guild)
+        .await
+        .ok_or(PlayError::SoundNotFound)?;
+    play(songbird, &soundboard, &now_playing, &events, guild, sound).await
 }
 
 async fn play_last_played_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(now_playing): State<Arc<NowPlaying>>,
+    State(events): State<Arc<Events>>,
     State(history): State<Arc<History>>,
-    Path(guild): Path<GuildId>,
-) -> StatusCode {
-    let Some(sound) = history.get_latest_played(guild, 0).await else {
-        return StatusCode::NOT_FOUND;
-    };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    ApiPath(guild): ApiPath<GuildId>,
+) -> ApiResult<Json<PlayedJson>> {
+    let sound = history
+        .get_latest_played(guild, 0)
+        .await
+        .ok_or(PlayError::SoundNotFound)?;
+    play(songbird, &soundboard, &now_playing, &events, guild, sound).await
 }
 
 async fn play_last_played_offset_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(now_playing): State<Arc<NowPlaying>>,
+    State(events): State<Arc<Events>>,
     State(history): State<Arc<History>>,
-    Path((guild, offset)): Path<(GuildId, usize)>,
-) -> StatusCode {
-    let Some(sound) = history.get_latest_played(guild, offset).await else {
-        return StatusCode::NOT_FOUND;
-    };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    ApiPath((guild, offset)): ApiPath<(GuildId, usize)>,
+) -> ApiResult<Json<PlayedJson>> {
+    let sound = history
+        .get_latest_played(guild, offset)
+        .await
+        .ok_or(PlayError::SoundNotFound)?;
+    play(songbird, &soundboard, &now_playing, &events, guild, sound).await
+}
+
+async fn play(
+    songbird: Arc<Songbird>,
+    soundboard: &Soundboard,
+    now_playing: &NowPlaying,
+    events: &Events,
+    guild: GuildId,
+    sound: Ulid,
+) -> ApiResult<Json<PlayedJson>> {
+    super::play_sound(songbird, soundboard, now_playing, guild, sound).await?;
+    if let Some(details) = soundboard.details(guild, sound).await {
+        events.publish(
+            guild,
+            GuildEvent::SoundPlayed {
+                id: sound,
+                name: details.metadata.name,
+                user: None,
+                source: PlaySource::Api,
+            },
+        );
+    }
+    Ok(Json(PlayedJson { id: sound }))
+}
+
+async fn download_recording(
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<ApiSettings>,
+    ApiPath((guild, user)): ApiPath<(GuildId, UserId)>,
+    ApiQuery(query): ApiQuery<RecordingQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let duration = parse_duration(query.duration.as_deref())?;
+    let offset = parse_duration(query.offset.as_deref())?.unwrap_or_default();
+
+    let (tx, rx) = oneshot::channel::<Option<VecDeque<i16>>>();
+    send_recorder_action(
+        &recorder,
+        guild,
+        user,
+        RecorderAction::GetVoiceData(user, tx),
+    )
+    .await?;
+    let data = Vec::from(
+        rx.await
+            .map_err(|_| ApiError::RecorderUnavailable)?
+            .ok_or(ApiError::NoVoiceData)?,
+    );
+
+    let end = data.len().saturating_sub(samples(offset));
+    let start = duration.map_or(0, |duration| end.saturating_sub(samples(duration)));
+    if start >= end {
+        return Err(ApiError::NoVoiceData);
+    }
+    let audio = encode(&settings.ffmpeg_path, query.format, &data[start..end]).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{user}.{}\"",
+                    query.format.extension()
+                ),
+            ),
+        ],
+        audio,
+    ))
+}
+
+/// Chunks of voice, split on silences, as a ZIP archive.
+async fn download_recording_chunks(
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<ApiSettings>,
+    ApiPath((guild, user)): ApiPath<(GuildId, UserId)>,
+    ApiQuery(query): ApiQuery<RecordingChunksQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let min_duration =
+        parse_duration(query.min_duration.as_deref())?.unwrap_or(DEFAULT_CHUNK_MIN_DURATION);
+    let count = query.count.unwrap_or(DEFAULT_RECORDING_CHUNKS);
+
+    let (tx, rx) = oneshot::channel::<Option<Vec<Vec<i16>>>>();
+    send_recorder_action(
+        &recorder,
+        guild,
+        user,
+        RecorderAction::GetVoiceDataChunks(user, count, min_duration, tx),
+    )
+    .await?;
+    let chunks = rx
+        .await
+        .map_err(|_| ApiError::RecorderUnavailable)?
+        .ok_or(ApiError::NoVoiceData)?;
+
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    for (i, chunk) in chunks.iter().enumerate() {
+        let audio = encode(&settings.ffmpeg_path, query.format, chunk).await?;
+        archive
+            .start_file(
+                format!("{user}-{}.{}", i + 1, query.format.extension()),
+                ZipFileOptions::default(),
+            )
+            .map_err(|_| ApiError::EncodingFailed)?;
+        archive
+            .write_all(&audio)
+            .map_err(|_| ApiError::EncodingFailed)?;
+    }
+    let archive = archive
+        .finish()
+        .map_err(|_| ApiError::EncodingFailed)?
+        .into_inner();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{user}.zip\""),
+            ),
+        ],
+        archive,
+    ))
+}
+
+/// Send an action to the recorder of a guild, only for whitelisted users like
+/// the recorder does.
+async fn send_recorder_action(
+    recorder: &Mutex<Recorder>,
+    guild: GuildId,
+    user: UserId,
+    action: RecorderAction,
+) -> ApiResult<()> {
+    let mut recorder = recorder.lock().await;
+    if !recorder.is_whitelisted(user) {
+        return Err(ApiError::NotWhitelisted);
+    }
+    recorder
+        .get_guild_recorder(guild)
+        .await
+        .send(action)
+        .map_err(|_| ApiError::RecorderUnavailable)
+}
+
+fn samples(duration: Duration) -> usize {
+    (duration.as_secs_f64() * FREQUENCY as f64) as usize
+}
+
+async fn encode(ffmpeg_path: &FsPath, format: AudioFormat, pcm: &[i16]) -> ApiResult<Vec<u8>> {
+    let wav = wav::package(pcm);
+    if let AudioFormat::Wav = format {
+        return Ok(wav);
+    }
+
+    let mut child = Command::new(ffmpeg_path)
+        .args(["-f", "wav"]) // Input file format.
+        .args(["-i", "-"]) // Read from stdin.
+        .args(["-c:a", "libopus"])
+        .args(["-f", "ogg"])
+        .arg("-") // Output to stdout.
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| {
+            warn!("failed to start ffmpeg: {err}");
+            ApiError::EncodingFailed
+        })?;
+    let mut stdin = child.stdin.take().ok_or(ApiError::EncodingFailed)?;
+    tokio::spawn(async move {
+        stdin
+            .write_all(&wav)
+            .await
+            .expect("Failed to write recording to ffmpeg");
+    });
+
+    let out = child
+        .wait_with_output()
+        .await
+        .map_err(|_| ApiError::EncodingFailed)?;
+    if !out.status.success() {
+        return Err(ApiError::EncodingFailed);
+    }
+    Ok(out.stdout)
+}
+
+/// Server-Sent Events stream of what happens in a guild, one JSON object per
+/// event with its `type`.
+async fn guild_events(
+    State(events): State<Arc<Events>>,
+    ApiPath(guild): ApiPath<GuildId>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let stream = stream::unfold(events.subscribe(), move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok((event_guild, event)) if event_guild == guild => {
+                    let event = SseEvent::default()
+                        .json_data(&event)
+                        .expect("Failed to serialize event");
+                    return Some((Ok(event), receiver));
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("api: event stream of guild {guild} missed {missed} events");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
-async fn play_sound_id(
+/// Metrics in the Prometheus text format.
+async fn metrics(
     State(songbird): State<Arc<Songbird>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
     State(soundboard): State<Arc<Soundboard>>,
-    Path((guild, sound)): Path<(GuildId, Ulid)>,
-) -> StatusCode {
-    if super::play_sound(songbird, &soundboard, guild, sound).await {
-        StatusCode::OK
-    } else {
-        StatusCode::NOT_FOUND
+    State(metrics): State<Arc<Metrics>>,
+) -> impl IntoResponse {
+    let mut writer = MetricsWriter::default();
+    metrics.write_counters(&mut writer);
+
+    let transcoding = soundboard.transcoding_stats();
+    writer.metric(
+        "disrecord_transcodings_total",
+        "counter",
+        "Uploaded sounds transcoded by ffmpeg.",
+        [
+            (
+                metrics::labels(&[("result", "success")]),
+                transcoding.successes as f64,
+            ),
+            (
+                metrics::labels(&[("result", "failure")]),
+                transcoding.failures as f64,
+            ),
+        ],
+    );
+    writer.value(
+        "disrecord_transcoding_seconds_total",
+        "counter",
+        "Time spent transcoding uploaded sounds.",
+        transcoding.duration.as_secs_f64(),
+    );
+
+    let cache = soundboard.cache_stats();
+    writer.value(
+        "disrecord_sound_cache_entries",
+        "gauge",
+        "Decoded sounds in the cache.",
+        cache.entries as f64,
+    );
+    writer.value(
+        "disrecord_sound_cache_bytes",
+        "gauge",
+        "Size of the decoded sounds in the cache.",
+        cache.size as f64,
+    );
+    writer.value(
+        "disrecord_sound_cache_max_bytes",
+        "gauge",
+        "Budget of the sounds cache.",
+        cache.max_size as f64,
+    );
+    writer.value(
+        "disrecord_sound_cache_hits_total",
+        "counter",
+        "Sounds played from the cache.",
+        cache.hits as f64,
+    );
+    writer.value(
+        "disrecord_sound_cache_misses_total",
+        "counter",
+        "Sounds read from disk.",
+        cache.misses as f64,
+    );
+    let lookups = cache.hits + cache.misses;
+    writer.value(
+        "disrecord_sound_cache_hit_ratio",
+        "gauge",
+        "Ratio of sounds played from the cache.",
+        if lookups == 0 {
+            0.0
+        } else {
+            cache.hits as f64 / lookups as f64
+        },
+    );
+
+    let guild_recorders = recorder
+        .lock()
+        .await
+        .guild_recorders()
+        .map(|(guild, recorder)| (guild, recorder.clone()))
+        .collect::<Vec<_>>();
+    writer.value(
+        "disrecord_guild_recorders",
+        "gauge",
+        "Guilds with an active recorder.",
+        guild_recorders.len() as f64,
+    );
+    let mut recorder_stats = Vec::with_capacity(guild_recorders.len());
+    for (guild, recorder) in guild_recorders {
+        let (tx, rx) = oneshot::channel();
+        if recorder.send(RecorderAction::GetStats(tx)).is_err() {
+            continue;
+        }
+        // Skip recorders that ended or are too busy to answer.
+        let Ok(Ok(stats)) = tokio::time::timeout(RECORDER_STATS_TIMEOUT, rx).await else {
+            warn!("api: no stats from the recorder of guild {guild}");
+            continue;
+        };
+        recorder_stats.push((metrics::labels(&[("guild", &guild.to_string())]), stats));
+    }
+    writer.metric(
+        "disrecord_voice_buffered_bytes",
+        "gauge",
+        "Voice data kept in the recording buffers.",
+        recorder_stats
+            .iter()
+            .map(|(labels, stats)| (labels.clone(), stats.buffered_bytes as f64)),
+    );
+    writer.metric(
+        "disrecord_voice_packets_total",
+        "counter",
+        "Voice packets received.",
+        recorder_stats
+            .iter()
+            .map(|(labels, stats)| (labels.clone(), stats.voice_packets as f64)),
+    );
+
+    let calls = songbird.iter().map(|(_, call)| call).collect::<Vec<_>>();
+    let mut connected = 0;
+    for call in calls {
+        if call.lock().await.current_channel().is_some() {
+            connected += 1;
+        }
     }
+    writer.value(
+        "disrecord_voice_calls",
+        "gauge",
+        "Connected voice calls.",
+        connected as f64,
+    );
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        writer.finish(),
+    )
 }
 
-pub fn router(state: ApiState) -> Router {
-    Router::new()
+/// Middleware counting requests by route and status.
+async fn count_requests(
+    State(metrics): State<Arc<Metrics>>,
+    matched_path: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let response = next.run(request).await;
+    if let Some(route) = matched_path {
+        metrics.count_request(&method, route.as_str(), response.status().as_u16());
+    }
+    response
+}
+
+/// The web soundboard, a single page using the API with the token of the
+/// login link given by `/soundboard web`.
+async fn web_soundboard() -> Html<&'static str> {
+    Html(include_str!("../web/index.html"))
+}
+
+/// Routes are grouped by the scope an API token needs to call them. Reading
+/// sounds only requires the play scope, and metrics a metrics token unless
+/// they are public.
+pub fn router(state: ApiState, tokens: Arc<ApiTokens>, metrics_public: bool) -> Router {
+    let join = Router::new()
         .route(
             "/guilds/{guild}/channels/{channel}/join",
             routing::post(join_channel),
@@ -154,6 +1201,21 @@ pub fn router(state: ApiState) -> Router {
             "/guilds/{guild}/users/{user}/follow",
             routing::post(join_user_channel),
         )
+        .route("/guilds/{guild}/leave", routing::post(leave_channel))
+        .route_layer(middleware::from_fn_with_state(
+            (tokens.clone(), Scope::Join),
+            auth::authorize,
+        ));
+    let play = Router::new()
+        .route("/guilds/{guild}/sounds", routing::get(list_sounds))
+        .route("/guilds/{guild}/sounds/{sound}", routing::get(get_sound))
+        .route(
+            "/guilds/{guild}/sounds/{sound}/wav",
+            routing::get(download_sound),
+        )
+        .route("/guilds/{guild}/groups", routing::get(list_groups))
+        .route("/guilds/{guild}/voice", routing::get(voice_status))
+        .route("/guilds/{guild}/events", routing::get(guild_events))
         .route(
             "/guilds/{guild}/sounds/{sound}/play",
             routing::post(play_sound),
@@ -174,5 +1236,52 @@ pub fn router(state: ApiState) -> Router {
             "/guilds/{guild}/sounds/last-played/{offset}/play",
             routing::post(play_last_played_offset_sound),
         )
+        .route_layer(middleware::from_fn_with_state(
+            (tokens.clone(), Scope::Play),
+            auth::authorize,
+        ));
+    let manage = Router::new()
+        .route(
+            "/guilds/{guild}/sounds",
+            routing::post(upload_sound).layer(DefaultBodyLimit::max(MAX_FILE_SIZE)),
+        )
+        .route(
+            "/guilds/{guild}/sounds/{sound}",
+            routing::patch(update_sound).delete(delete_sound),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            (tokens.clone(), Scope::Manage),
+            auth::authorize,
+        ));
+    let mut metrics = Router::new().route("/metrics", routing::get(self::metrics));
+    if !metrics_public {
+        metrics = metrics.route_layer(middleware::from_fn_with_state(
+            tokens.clone(),
+            auth::authorize_metrics,
+        ));
+    }
+    let record = Router::new()
+        .route(
+            "/guilds/{guild}/users/{user}/recording",
+            routing::get(download_recording),
+        )
+        .route(
+            "/guilds/{guild}/users/{user}/recording/chunks",
+            routing::get(download_recording_chunks),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            (tokens, Scope::Record),
+            auth::authorize,
+        ));
+
+    let counters = Arc::clone(&state.metrics);
+    Router::new()
+        .route("/web", routing::get(web_soundboard))
+        .merge(metrics)
+        .merge(join)
+        .merge(play)
+        .merge(manage)
+        .merge(record)
+        .layer(middleware::from_fn_with_state(counters, count_requests))
         .with_state(state)
 }
@@ -0,0 +1,243 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path as FsPath,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::{info, warn};
+use serde::Deserialize;
+use serenity::all::GuildId;
+
+use crate::api::ApiError;
+
+/// How long a login link of the web soundboard stays valid.
+const WEB_SESSION_DURATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Actions an API token may be allowed to perform in a guild.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Play,
+    Join,
+    Manage,
+    Record,
+}
+
+/// Static API tokens, read once from a JSON file:
+/// `[{"name": "stream-deck", "token": "...", "guilds": {"<guild>": ["play"]}}]`,
+/// optionally with `"metrics": true` to read the metrics of all guilds, and
+/// the sessions of the web soundboard, kept in memory only.
+#[derive(Default, Debug)]
+pub struct ApiTokens {
+    /// The API is open to everyone without a tokens file.
+    tokens: Option<HashMap<String, ApiToken>>,
+    sessions: Mutex<HashMap<String, (ApiToken, Instant)>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct ApiToken {
+    name: String,
+    token: String,
+    #[serde(default)]
+    guilds: HashMap<GuildId, HashSet<Scope>>,
+    #[serde(default)]
+    metrics: bool,
+}
+
+impl ApiTokens {
+    pub async fn load(path: &FsPath) -> Self {
+        let tokens = serde_json::from_slice::<Vec<ApiToken>>(
+            &tokio::fs::read(path)
+                .await
+                .expect("Failed to read API tokens file"),
+        )
+        .expect("Invalid API tokens file");
+        info!("loaded {} API tokens", tokens.len());
+        Self {
+            tokens: Some(
+                tokens
+                    .into_iter()
+                    .map(|token| (token.token.clone(), token))
+                    .collect(),
+            ),
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Create a token allowed to play sounds in a guild, for the login link of
+    /// the web soundboard.
+    pub fn create_web_session(&self, guild: GuildId, user: &str) -> String {
+        let secret = rand::random::<[u8; 24]>()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        let token = ApiToken {
+            name: format!("web:{user}"),
+            token: secret.clone(),
+            guilds: HashMap::from([(guild, HashSet::from([Scope::Play]))]),
+            metrics: false,
+        };
+
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().expect("Sessions lock poisoned");
+        sessions.retain(|_, (_, expiration)| *expiration > now);
+        sessions.insert(secret.clone(), (token, now + WEB_SESSION_DURATION));
+        info!("created web session for user {user} in guild {guild}");
+        secret
+    }
+
+    fn authenticate(&self, secret: &str) -> Option<ApiToken> {
+        if let Some(token) = self.tokens.as_ref().and_then(|tokens| tokens.get(secret)) {
+            return Some(token.clone());
+        }
+        self.sessions
+            .lock()
+            .expect("Sessions lock poisoned")
+            .get(secret)
+            .filter(|(_, expiration)| *expiration > Instant::now())
+            .map(|(token, _)| token.clone())
+    }
+
+    fn authenticate_request(&self, request: &Request) -> Option<ApiToken> {
+        request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|secret| self.authenticate(secret))
+    }
+}
+
+impl ApiToken {
+    fn allows(&self, guild: GuildId, scope: Scope) -> bool {
+        self.guilds
+            .get(&guild)
+            .is_some_and(|scopes| scopes.contains(&scope))
+    }
+}
+
+/// Middleware checking that the bearer token of the request grants `scope` in
+/// the guild of the route. Requests pass through if no tokens file is
+/// configured.
+pub async fn authorize(
+    State((tokens, scope)): State<(Arc<ApiTokens>, Scope)>,
+    Path(params): Path<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if tokens.tokens.is_none() {
+        return next.run(request).await;
+    }
+
+    let Some(token) = tokens.authenticate_request(&request) else {
+        warn!(
+            "api: unauthenticated request to {} {}",
+            request.method(),
+            request.uri().path()
+        );
+        return ApiError::Unauthorized.into_response();
+    };
+    let Some(guild) = params
+        .get("guild")
+        .and_then(|guild| guild.parse::<u64>().ok())
+        .filter(|guild| *guild != 0)
+        .map(GuildId::new)
+    else {
+        return ApiError::InvalidRequest("Invalid guild id.".to_owned()).into_response();
+    };
+    if !token.allows(guild, scope) {
+        warn!(
+            "api: token \"{}\" denied {} {}",
+            token.name,
+            request.method(),
+            request.uri().path()
+        );
+        return ApiError::Forbidden.into_response();
+    }
+
+    info!(
+        "api: token \"{}\" {} {}",
+        token.name,
+        request.method(),
+        request.uri().path()
+    );
+    next.run(request).await
+}
+
+/// Middleware checking that the bearer token of the request may read the
+/// metrics, which cover every guild. Requests pass through if no tokens file
+/// is configured.
+pub async fn authorize_metrics(
+    State(tokens): State<Arc<ApiTokens>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if tokens.tokens.is_none() {
+        return next.run(request).await;
+    }
+
+    let Some(token) = tokens.authenticate_request(&request) else {
+        warn!("api: unauthenticated request to {}", request.uri().path());
+        return ApiError::Unauthorized.into_response();
+    };
+    if !token.metrics {
+        warn!(
+            "api: token \"{}\" denied {}",
+            token.name,
+            request.uri().path()
+        );
+        return ApiError::Forbidden.into_response();
+    }
+    // Not logged, metrics are scraped every few seconds.
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::GuildId;
+
+    use super::{ApiToken, ApiTokens, Scope};
+
+    #[test]
+    fn scopes() {
+        let tokens = serde_json::from_str::<Vec<ApiToken>>(
+            r#"[{"name": "deck", "token": "secret", "guilds": {"42": ["play", "join"]}}]"#,
+        )
+        .expect("Failed to parse tokens");
+        let token = &tokens[0];
+        assert!(token.allows(GuildId::new(42), Scope::Play));
+        assert!(!token.allows(GuildId::new(42), Scope::Manage));
+        assert!(!token.allows(GuildId::new(43), Scope::Play));
+        assert!(!token.metrics);
+    }
+
+    #[test]
+    fn metrics_token() {
+        let tokens = serde_json::from_str::<Vec<ApiToken>>(
+            r#"[{"name": "prometheus", "token": "secret", "metrics": true}]"#,
+        )
+        .expect("Failed to parse tokens");
+        let token = &tokens[0];
+        assert!(token.metrics);
+        assert!(!token.allows(GuildId::new(42), Scope::Play));
+    }
+
+    #[test]
+    fn web_sessions() {
+        let tokens = ApiTokens::default();
+        let secret = tokens.create_web_session(GuildId::new(42), "alice");
+        let token = tokens.authenticate(&secret).expect("Session not found");
+        assert_eq!(token.name, "web:alice");
+        assert!(token.allows(GuildId::new(42), Scope::Play));
+        assert!(!token.allows(GuildId::new(42), Scope::Join));
+        assert!(!token.metrics);
+        assert!(tokens.authenticate("unknown").is_none());
+    }
+}
@@ -0,0 +1,543 @@
+//! Offline maintenance of the soundboard store. The bot must be stopped while
+//! running commands that write, as it keeps its own copy of the metadata.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use itertools::Itertools;
+use ulid::Ulid;
+use zip::{ZipArchive, ZipWriter, write::FileOptions as ZipFileOptions};
+
+use crate::store::{
+    BACKUP_METADATA_NAME, BackupGroup, BackupSound, INDEX_GAP, METADATA_VERSION, Metadata,
+    SoundMetadata, SoundMetadataV1, file_path,
+};
+
+#[allow(dead_code)]
+#[path = "../button.rs"]
+mod button;
+#[allow(dead_code)]
+#[path = "../store.rs"]
+mod store;
+#[allow(dead_code)]
+#[path = "../wav.rs"]
+mod wav;
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Options {
+    #[arg(short = 's', long, default_value("soundboard"))]
+    soundboard_metadata_path: PathBuf,
+    #[arg(short = 'S', long, default_value("."))]
+    sounds_dir_path: PathBuf,
+    #[command(subcommand)]
+    command: AdminCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum AdminCommand {
+    /// List guilds, or the sounds of a guild.
+    List {
+        #[arg(short, long)]
+        guild: Option<u64>,
+    },
+    /// Export the sounds of a guild as a ZIP backup, like `/soundboard backup`.
+    Export {
+        #[arg(short, long)]
+        guild: u64,
+        archive: PathBuf,
+    },
+    /// Import a ZIP backup in a guild, skipping sounds that already exist.
+    Import {
+        #[arg(short, long)]
+        guild: u64,
+        archive: PathBuf,
+    },
+    /// Check that every sound has a valid file and every file is used.
+    Fsck,
+    /// Remove orphaned files, and optionally sounds without a file. Only lists
+    /// the changes without `--yes`.
+    Repair {
+        #[arg(long)]
+        drop_missing: bool,
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Convert the metadata file to another format version.
+    Migrate {
+        #[arg(long, default_value_t = METADATA_VERSION)]
+        to: u16,
+    },
+}
+
+fn main() -> ExitCode {
+    let options = Options::parse();
+    match &options.command {
+        AdminCommand::List { guild } => list(&options, *guild),
+        AdminCommand::Export { guild, archive } => export(&options, *guild, archive),
+        AdminCommand::Import { guild, archive } => import(&options, *guild, archive),
+        AdminCommand::Fsck => fsck(&options),
+        AdminCommand::Repair { drop_missing, yes } => repair(&options, *drop_missing, *yes),
+        AdminCommand::Migrate { to } => migrate(&options, *to),
+    }
+}
+
+fn list(options: &Options, guild: Option<u64>) -> ExitCode {
+    let Some(sounds) = load(options, true) else {
+        return ExitCode::FAILURE;
+    };
+
+    match guild {
+        None => {
+            for (guild, sounds) in sounds
+                .iter()
+                .into_group_map_by(|sound| sound.guild)
+                .into_iter()
+                .sorted_by_key(|(guild, _)| *guild)
+            {
+                let groups = sounds.iter().map(|sound| &sound.group).unique().count();
+                println!("{guild}: {} sounds in {groups} groups", sounds.len());
+            }
+        }
+        Some(guild) => {
+            for (group, sounds) in guild_groups(&sounds, guild) {
+                println!("{group}");
+                for sound in sounds {
+                    println!(
+                        "  {:>3} {} {} [{}] {}",
+                        sound.index,
+                        sound.id,
+                        sound.name,
+                        button::as_str(sound.color),
+                        sound.emoji.as_deref().unwrap_or_default(),
+                    );
+                }
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn export(options: &Options, guild: u64, archive_path: &Path) -> ExitCode {
+    let Some(sounds) = load(options, true) else {
+        return ExitCode::FAILURE;
+    };
+
+    let groups = guild_groups(&sounds, guild);
+    if groups.is_empty() {
+        eprintln!("There is no sounds for guild {guild}.");
+        return ExitCode::FAILURE;
+    }
+
+    let backup = groups
+        .iter()
+        .map(|(group, sounds)| BackupGroup {
+            group: group.to_string(),
+            sounds: sounds
+                .iter()
+                .map(|sound| BackupSound {
+                    id: sound.id,
+                    name: sound.name.clone(),
+                    emoji: sound.emoji.clone(),
+                    color: button::as_str(sound.color).to_owned(),
+                })
+                .collect(),
+        })
+        .collect_vec();
+
+    let mut archive =
+        ZipWriter::new(File::create(archive_path).expect("Failed to create backup archive"));
+    archive
+        .start_file(BACKUP_METADATA_NAME, ZipFileOptions::default())
+        .expect("Failed to write backup archive");
+    archive
+        .write_all(
+            serde_json::to_string_pretty(&backup)
+                .expect("Failed to serialize backup metadata")
+                .as_bytes(),
+        )
+        .expect("Failed to write backup archive");
+    for sound in groups.into_iter().flat_map(|(_, sounds)| sounds) {
+        let data = match fs::read(file_path(&options.sounds_dir_path, sound.file)) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Failed to read file of sound {}: {err}", sound.id);
+                return ExitCode::FAILURE;
+            }
+        };
+        archive
+            .start_file(store::backup_file_name(sound.id), ZipFileOptions::default())
+            .expect("Failed to write backup archive");
+        archive
+            .write_all(&data)
+            .expect("Failed to write backup archive");
+    }
+    archive.finish().expect("Failed to write backup archive");
+
+    println!(
+        "Exported {} sounds to {}.",
+        sounds.iter().filter(|sound| sound.guild == guild).count(),
+        archive_path.display()
+    );
+    ExitCode::SUCCESS
+}
+
+fn import(options: &Options, guild: u64, archive_path: &Path) -> ExitCode {
+    let Some(mut sounds) = load(options, false) else {
+        return ExitCode::FAILURE;
+    };
+
+    let mut archive = ZipArchive::new(File::open(archive_path).expect("Failed to open archive"))
+        .expect("Failed to read archive");
+    let backup = {
+        let mut entry = archive
+            .by_name(BACKUP_METADATA_NAME)
+            .expect("Archive is missing its metadata");
+        let mut data = Vec::new();
+        entry
+            .read_to_end(&mut data)
+            .expect("Failed to read archive metadata");
+        serde_json::from_slice::<Vec<BackupGroup>>(&data).expect("Invalid archive metadata")
+    };
+
+    let mut ids = sounds
+        .iter()
+        .map(|sound| (sound.id, sound.guild))
+        .collect::<HashMap<_, _>>();
+    let mut files = sounds
+        .iter()
+        .filter_map(|sound| Some((sound.hash?, sound.file)))
+        .collect::<HashMap<_, _>>();
+    let (mut imported, mut skipped) = (0, 0);
+    for backup_group in backup {
+        // Reuse the spelling of an existing group, like uploads do.
+        let group = sounds
+            .iter()
+            .find(|sound| {
+                sound.guild == guild
+                    && sound.group.to_lowercase() == backup_group.group.to_lowercase()
+            })
+            .map(|sound| sound.group.clone())
+            .unwrap_or(backup_group.group);
+
+        for backup_sound in backup_group.sounds {
+            // Keep ids when restoring a backup, but not when copying sounds
+            // from another guild.
+            let id = match ids.get(&backup_sound.id) {
+                Some(sound_guild) if *sound_guild == guild => {
+                    println!("Skipping {}: sound already exists.", backup_sound.name);
+                    skipped += 1;
+                    continue;
+                }
+                Some(_) => Ulid::new(),
+                None => backup_sound.id,
+            };
+            if sounds.iter().any(|sound| {
+                sound.guild == guild
+                    && sound.group == group
+                    && sound.name.to_lowercase() == backup_sound.name.to_lowercase()
+            }) {
+                println!("Skipping {}: name already taken.", backup_sound.name);
+                skipped += 1;
+                continue;
+            }
+
+            let mut data = Vec::new();
+            let read = archive
+                .by_name(&store::backup_file_name(backup_sound.id))
+                .and_then(|mut entry| Ok(entry.read_to_end(&mut data)?));
+            if read.is_err() || !wav::is_valid_pcm_s16le(&data) {
+                println!("Skipping {}: missing or invalid file.", backup_sound.name);
+                skipped += 1;
+                continue;
+            }
+
+            let hash = wav::pcm_hash(&data);
+            let file = match files.get(&hash) {
+                Some(file) => *file,
+                None => {
+                    fs::write(file_path(&options.sounds_dir_path, id), &data)
+                        .expect("Failed to write sound file");
+                    files.insert(hash, id);
+                    id
+                }
+            };
+            let index = sounds
+                .iter()
+                .filter(|sound| sound.guild == guild && sound.group == group)
+                .map(|sound| sound.index + INDEX_GAP)
+                .max()
+                .unwrap_or(INDEX_GAP);
+            ids.insert(id, guild);
+            sounds.push(SoundMetadata {
+                guild,
+                id,
+                file,
+                hash: Some(hash),
+                name: backup_sound.name,
+                emoji: backup_sound.emoji,
+                color: button::parse_color(&backup_sound.color),
+                group: group.clone(),
+                index,
+            });
+            imported += 1;
+        }
+    }
+
+    save(options, &sounds);
+    println!("Imported {imported} sounds, skipped {skipped}.");
+    ExitCode::SUCCESS
+}
+
+fn fsck(options: &Options) -> ExitCode {
+    let Some(sounds) = load(options, true) else {
+        return ExitCode::FAILURE;
+    };
+
+    let mut problems = 0;
+    for sound in &sounds {
+        let Ok(data) = fs::read(file_path(&options.sounds_dir_path, sound.file)) else {
+            println!(
+                "Missing file {} of sound {} ({}).",
+                sound.file, sound.id, sound.name
+            );
+            problems += 1;
+            continue;
+        };
+        if !wav::is_valid_pcm_s16le(&data) {
+            println!(
+                "Invalid file {} of sound {} ({}).",
+                sound.file, sound.id, sound.name
+            );
+            problems += 1;
+        } else if sound.hash.is_some_and(|hash| hash != wav::pcm_hash(&data)) {
+            println!(
+                "Altered file {} of sound {} ({}).",
+                sound.file, sound.id, sound.name
+            );
+            problems += 1;
+        }
+    }
+    for path in orphaned_files(options, &sounds) {
+        println!("Orphaned file {}.", path.display());
+        problems += 1;
+    }
+
+    if problems == 0 {
+        println!("Checked {} sounds, no problem found.", sounds.len());
+        ExitCode::SUCCESS
+    } else {
+        println!(
+            "Checked {} sounds, found {problems} problems.",
+            sounds.len()
+        );
+        ExitCode::FAILURE
+    }
+}
+
+fn repair(options: &Options, drop_missing: bool, yes: bool) -> ExitCode {
+    // Without metadata every sound file would look orphaned.
+    let Some(mut sounds) = load(options, false) else {
+        return ExitCode::FAILURE;
+    };
+
+    let mut changes = 0;
+    for path in orphaned_files(options, &sounds) {
+        changes += 1;
+        if !yes {
+            println!("Would remove orphaned file {}.", path.display());
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => println!("Removed orphaned file {}.", path.display()),
+            Err(err) => eprintln!("Failed to remove {}: {err}", path.display()),
+        }
+    }
+
+    if drop_missing {
+        let count = sounds.len();
+        sounds.retain(|sound| {
+            let exists = file_path(&options.sounds_dir_path, sound.file).exists();
+            if !exists {
+                if yes {
+                    println!("Dropped sound {} ({}) without file.", sound.id, sound.name);
+                } else {
+                    println!(
+                        "Would drop sound {} ({}) without file.",
+                        sound.id, sound.name
+                    );
+                }
+            }
+            exists
+        });
+        changes += count - sounds.len();
+        if yes && sounds.len() != count {
+            save(options, &sounds);
+        }
+    }
+
+    if changes == 0 {
+        println!("Nothing to repair.");
+    } else if !yes {
+        println!("Run again with --yes to apply these {changes} changes.");
+    }
+    ExitCode::SUCCESS
+}
+
+fn migrate(options: &Options, to: u16) -> ExitCode {
+    let metadata = match fs::read(&options.soundboard_metadata_path) {
+        Ok(file) => match store::decode_metadata(&file) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                eprintln!("{err}");
+                return ExitCode::FAILURE;
+            }
+        },
+        Err(err) => {
+            eprintln!("Failed to read metadata file: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let from = metadata.version();
+    if from == to {
+        println!("Metadata file already uses format version {to}.");
+        return ExitCode::SUCCESS;
+    }
+
+    let data = match (metadata, to) {
+        (Metadata::V1(sounds), 2) => {
+            let sounds = sounds
+                .into_iter()
+                .map(|sound| {
+                    let hash = fs::read(file_path(&options.sounds_dir_path, sound.id))
+                        .ok()
+                        .map(|data| wav::pcm_hash(&data));
+                    if hash.is_none() {
+                        println!("Missing file of sound {} ({}).", sound.id, sound.name);
+                    }
+                    sound.upgrade(hash)
+                })
+                .collect_vec();
+            store::encode_metadata(&sounds)
+        }
+        (Metadata::V2(sounds), 1) => {
+            // The legacy format has no shared files, give each sound its own copy.
+            for sound in sounds.iter().filter(|sound| sound.file != sound.id) {
+                fs::copy(
+                    file_path(&options.sounds_dir_path, sound.file),
+                    file_path(&options.sounds_dir_path, sound.id),
+                )
+                .expect("Failed to copy shared sound file");
+            }
+            let sounds = sounds.into_iter().map(SoundMetadataV1::from).collect_vec();
+            store::encode_metadata_v1(&sounds)
+        }
+        _ => {
+            eprintln!("Cannot migrate from format version {from} to {to}.");
+            return ExitCode::FAILURE;
+        }
+    }
+    .expect("Failed to encode metadata");
+
+    let backup_path = options
+        .soundboard_metadata_path
+        .with_extension(format!("v{from}"));
+    fs::copy(&options.soundboard_metadata_path, &backup_path)
+        .expect("Failed to backup metadata file");
+    write_metadata(options, &data);
+    println!(
+        "Migrated metadata file from format version {from} to {to}, previous file kept as {}.",
+        backup_path.display()
+    );
+    ExitCode::SUCCESS
+}
+
+/// Read sounds metadata, refusing files in another format than the one of the
+/// bot. A missing file is an empty soundboard only when `allow_missing`, for
+/// commands that do not write.
+fn load(options: &Options, allow_missing: bool) -> Option<Vec<SoundMetadata>> {
+    let file = match fs::read(&options.soundboard_metadata_path) {
+        Ok(file) => file,
+        Err(err) if allow_missing && err.kind() == ErrorKind::NotFound => return Some(Vec::new()),
+        Err(err) => {
+            eprintln!(
+                "Failed to read metadata file {}: {err}",
+                options.soundboard_metadata_path.display()
+            );
+            return None;
+        }
+    };
+    match store::decode_metadata(&file) {
+        Ok(Metadata::V2(sounds)) => Some(sounds),
+        Ok(metadata) => {
+            eprintln!(
+                "Metadata file uses format version {}, run the migrate command first.",
+                metadata.version()
+            );
+            None
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            None
+        }
+    }
+}
+
+fn save(options: &Options, sounds: &[SoundMetadata]) {
+    write_metadata(
+        options,
+        &store::encode_metadata(sounds).expect("Failed to encode metadata"),
+    );
+}
+
+/// Write through a temporary file, so an interruption never leaves a
+/// truncated metadata file.
+fn write_metadata(options: &Options, data: &[u8]) {
+    let tmp_path = options.soundboard_metadata_path.with_extension("tmp");
+    fs::write(&tmp_path, data).expect("Failed to write metadata file");
+    fs::rename(&tmp_path, &options.soundboard_metadata_path)
+        .expect("Failed to write metadata file");
+}
+
+/// Sounds of a guild by group, sorted like the bot lists them.
+fn guild_groups(sounds: &[SoundMetadata], guild: u64) -> Vec<(&str, Vec<&SoundMetadata>)> {
+    sounds
+        .iter()
+        .filter(|sound| sound.guild == guild)
+        .into_group_map_by(|sound| sound.group.as_str())
+        .into_iter()
+        .sorted_by_key(|(group, _)| *group)
+        .map(|(group, mut sounds)| {
+            sounds.sort_by_key(|sound| sound.index);
+            (group, sounds)
+        })
+        .collect()
+}
+
+/// WAV files named after a ULID that no sound uses.
+fn orphaned_files(options: &Options, sounds: &[SoundMetadata]) -> Vec<PathBuf> {
+    let used = sounds
+        .iter()
+        .map(|sound| sound.file)
+        .collect::<HashSet<_>>();
+    fs::read_dir(&options.sounds_dir_path)
+        .expect("Failed to read sounds directory")
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension() == Some(OsStr::new("wav"))
+                && path
+                    .file_stem()
+                    .and_then(OsStr::to_str)
+                    .and_then(|stem| Ulid::from_string(stem).ok())
+                    .is_some_and(|id| !used.contains(&id))
+        })
+        .sorted()
+        .collect()
+}
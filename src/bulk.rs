@@ -0,0 +1,260 @@
+use std::{
+    io::{Cursor, Read},
+    path::{Component, Path},
+    time::Duration,
+};
+
+use zip::ZipArchive;
+
+use crate::{soundboard::SoundboardError, wav};
+
+pub const MAX_BULK_SOUNDS: usize = 25;
+pub const MAX_BULK_ATTACHMENTS: usize = 10;
+const MAX_NAME_LENGTH: usize = 80;
+// Leave some room under the 2000 characters of a message.
+const MAX_SUMMARY_LENGTH: usize = 1900;
+
+/// An audio file of a bulk upload, either attached or found in an archive.
+pub struct BulkFile {
+    /// Name of the attachment, or path of the file in its archive.
+    pub path: String,
+    pub name: String,
+    /// Innermost folder of the file in its archive.
+    pub folder: Option<String>,
+    pub data: Result<Vec<u8>, SoundboardError>,
+}
+
+/// Outcome of the upload of one file, reported in the summary.
+pub struct BulkUpload {
+    pub path: String,
+    pub result: Result<BulkSound, SoundboardError>,
+}
+
+pub struct BulkSound {
+    pub name: String,
+    pub group: String,
+    /// Name of an identical sound of the guild.
+    pub duplicate: Option<String>,
+}
+
+pub fn is_archive(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(".zip")
+}
+
+/// Name of the sound of a file, without its extension, and its innermost
+/// folder. Hidden files and folders, like macOS metadata, are ignored.
+pub fn sound_name(path: &Path) -> Option<(String, Option<String>)> {
+    let hidden = path.components().any(|component| match component {
+        Component::Normal(part) => part
+            .to_str()
+            .is_none_or(|part| part.starts_with('.') || part == "__MACOSX"),
+        _ => false,
+    });
+    if hidden {
+        return None;
+    }
+
+    let name = path
+        .file_stem()?
+        .to_str()?
+        .trim()
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+    if name.is_empty() {
+        return None;
+    }
+    let folder = path
+        .parent()
+        .and_then(Path::file_name)
+        .and_then(|folder| folder.to_str())
+        .map(|folder| folder.trim().to_owned())
+        .filter(|folder| !folder.is_empty());
+    Some((name, folder))
+}
+
+/// List the first `limit` audio files of a ZIP archive, sorted by path, and
+/// count the ones left out. Files longer than the maximum duration once
+/// decoded are not extracted, whatever size the archive declares for them.
+pub fn read_archive(
+    data: Vec<u8>,
+    max_duration: Duration,
+    limit: usize,
+) -> Result<(Vec<BulkFile>, usize), SoundboardError> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(data)).map_err(|_| SoundboardError::InvalidArchive)?;
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(index)
+            .map_err(|_| SoundboardError::InvalidArchive)?;
+        if entry.is_dir() {
+            continue;
+        }
+        // Skip paths escaping the archive.
+        let Some(path) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        if let Some((name, folder)) = sound_name(&path) {
+            entries.push((index, path, name, folder));
+        }
+    }
+    entries.sort_by(|e1, e2| e1.1.cmp(&e2.1));
+    let left_out = entries.len().saturating_sub(limit);
+    entries.truncate(limit);
+
+    let max_size = wav::size_from_duration(max_duration);
+    let mut files = Vec::with_capacity(entries.len());
+    for (index, path, name, folder) in entries {
+        let entry = archive
+            .by_index(index)
+            .map_err(|_| SoundboardError::InvalidArchive)?;
+        let data = if entry.size() as usize > max_size {
+            Err(SoundboardError::TooLong)
+        } else {
+            // Declared sizes cannot be trusted, read one byte past the limit
+            // to catch longer files.
+            let mut data = Vec::with_capacity(entry.size() as usize);
+            match entry.take(max_size as u64 + 1).read_to_end(&mut data) {
+                Ok(_) if data.len() > max_size => Err(SoundboardError::TooLong),
+                Ok(_) => Ok(data),
+                Err(_) => Err(SoundboardError::InvalidArchive),
+            }
+        };
+        files.push(BulkFile {
+            path: path.to_string_lossy().into_owned(),
+            name,
+            folder,
+            data,
+        });
+    }
+    Ok((files, left_out))
+}
+
+/// Summarize a bulk upload in a single message, one line per file.
+pub fn summary(uploads: &[BulkUpload], left_out: usize) -> String {
+    let added = uploads
+        .iter()
+        .filter(|upload| upload.result.is_ok())
+        .count();
+    let mut summary = format!("Added {added} of {} sounds.", uploads.len() + left_out);
+    let lines = uploads.iter().map(|upload| match &upload.result {
+        Ok(BulkSound {
+            name,
+            group,
+            duplicate: None,
+        }) => format!("✅ **{name}** in **{group}**"),
+        Ok(BulkSound {
+            name,
+            group,
+            duplicate: Some(duplicate),
+        }) => format!("⚠️ **{name}** in **{group}**, identical to **{duplicate}**"),
+        Err(err) => format!("❌ `{}`: {err}", upload.path),
+    });
+    for line in lines {
+        if summary.len() + line.len() + 1 > MAX_SUMMARY_LENGTH {
+            summary.push_str("\n…");
+            break;
+        }
+        summary.push('\n');
+        summary.push_str(&line);
+    }
+    if left_out > 0 {
+        summary.push_str(&format!(
+            "\n{left_out} more files were left out, only {MAX_BULK_SOUNDS} sounds can be uploaded at once."
+        ));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Write},
+        path::Path,
+        time::Duration,
+    };
+
+    use zip::{ZipWriter, write::FileOptions};
+
+    use super::{BulkSound, BulkUpload, read_archive, sound_name, summary};
+    use crate::soundboard::SoundboardError;
+
+    #[test]
+    fn sound_names() {
+        assert_eq!(
+            sound_name(Path::new("pack/memes/Bruh.mp3")),
+            Some(("Bruh".to_owned(), Some("memes".to_owned())))
+        );
+        assert_eq!(
+            sound_name(Path::new("wow.ogg")),
+            Some(("wow".to_owned(), None))
+        );
+        assert_eq!(sound_name(Path::new("__MACOSX/memes/._Bruh.mp3")), None);
+        assert_eq!(sound_name(Path::new("memes/.DS_Store")), None);
+    }
+
+    #[test]
+    fn archive() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, size) in [
+            ("memes/b.wav", 10),
+            ("memes/", 0),
+            ("a.wav", 10),
+            ("long.wav", 1 << 24),
+        ] {
+            if path.ends_with('/') {
+                writer.add_directory(path, FileOptions::default()).unwrap();
+            } else {
+                writer.start_file(path, FileOptions::default()).unwrap();
+                writer.write_all(&vec![0; size]).unwrap();
+            }
+        }
+        let data = writer.finish().unwrap().into_inner();
+
+        let (files, left_out) = read_archive(data.clone(), Duration::from_secs(15), 10).unwrap();
+        assert_eq!(left_out, 0);
+        assert_eq!(
+            files
+                .iter()
+                .map(|file| (file.name.as_str(), file.folder.as_deref()))
+                .collect::<Vec<_>>(),
+            [("a", None), ("long", None), ("b", Some("memes"))]
+        );
+        assert_eq!(files[0].data.as_ref().unwrap().len(), 10);
+        assert!(matches!(files[1].data, Err(SoundboardError::TooLong)));
+
+        let (files, left_out) = read_archive(data, Duration::from_secs(15), 1).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "a");
+        assert_eq!(left_out, 2);
+
+        assert!(matches!(
+            read_archive(vec![0; 10], Duration::from_secs(15), 10),
+            Err(SoundboardError::InvalidArchive)
+        ));
+    }
+
+    #[test]
+    fn summaries() {
+        let uploads = [
+            BulkUpload {
+                path: "memes/bruh.mp3".to_owned(),
+                result: Ok(BulkSound {
+                    name: "bruh".to_owned(),
+                    group: "memes".to_owned(),
+                    duplicate: None,
+                }),
+            },
+            BulkUpload {
+                path: "long.wav".to_owned(),
+                result: Err(SoundboardError::TooLong),
+            },
+        ];
+        assert_eq!(
+            summary(&uploads, 0),
+            "Added 1 of 2 sounds.\n✅ **bruh** in **memes**\n❌ `long.wav`: Sound too long."
+        );
+        assert!(summary(&uploads, 3).starts_with("Added 1 of 5 sounds."));
+    }
+}
@@ -5,18 +5,22 @@ use std::{
 
 use serenity::{all::ButtonStyle, builder::CreateButton, model::channel::ReactionType};
 
-use crate::soundboard::SoundMetadata;
+use crate::store::SoundMetadata;
 
 const DEFAULT: ButtonStyle = ButtonStyle::Primary;
 const DEFAULT_STR: &str = "blue";
 
 pub fn parse_color(s: &str) -> ButtonStyle {
+    try_parse_color(s).unwrap_or(DEFAULT)
+}
+
+pub fn try_parse_color(s: &str) -> Option<ButtonStyle> {
     match s {
-        "blue" => ButtonStyle::Primary,
-        "green" => ButtonStyle::Success,
-        "red" => ButtonStyle::Danger,
-        "grey" => ButtonStyle::Secondary,
-        _ => DEFAULT,
+        "blue" => Some(ButtonStyle::Primary),
+        "green" => Some(ButtonStyle::Success),
+        "red" => Some(ButtonStyle::Danger),
+        "grey" => Some(ButtonStyle::Secondary),
+        _ => None,
     }
 }
 
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use lru::LruCache;
+use ulid::Ulid;
+
+/// Least recently used cache of decoded sounds, bounded by the total size of the
+/// cached data rather than by a number of entries.
+#[derive(Debug)]
+pub struct SoundCache {
+    entries: LruCache<Ulid, Arc<[u8]>>,
+    size: usize,
+    max_size: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl SoundCache {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            size: 0,
+            max_size,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Fetch a sound and mark it as the most recently used one.
+    pub fn get(&mut self, id: &Ulid) -> Option<Arc<[u8]>> {
+        match self.entries.get(id) {
+            Some(data) => {
+                self.hits += 1;
+                Some(Arc::clone(data))
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Insert a sound, evicting the least recently used ones until the cache
+    /// fits in its budget. Sounds larger than the whole budget are ignored.
+    pub fn insert(&mut self, id: Ulid, data: Arc<[u8]>) {
+        if data.len() > self.max_size {
+            return;
+        }
+        self.remove(&id);
+        self.size += data.len();
+        self.entries.put(id, data);
+        while self.size > self.max_size {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.size -= evicted.len(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: &Ulid) {
+        if let Some(data) = self.entries.pop(id) {
+            self.size -= data.len();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            size: self.size,
+            max_size: self.max_size,
+            hits: self.hits,
+            misses: self.misses,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub size: usize,
+    pub max_size: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ulid::Ulid;
+
+    use super::SoundCache;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache = SoundCache::new(10);
+        let (first, second, third) = (Ulid::new(), Ulid::new(), Ulid::new());
+        cache.insert(first, Arc::from(vec![0; 4]));
+        cache.insert(second, Arc::from(vec![0; 4]));
+        assert!(cache.get(&first).is_some());
+        cache.insert(third, Arc::from(vec![0; 4]));
+
+        assert!(cache.entries.contains(&first));
+        assert!(!cache.entries.contains(&second));
+        assert!(cache.entries.contains(&third));
+        assert_eq!(cache.stats().size, 8);
+    }
+
+    #[test]
+    fn ignore_too_large() {
+        let mut cache = SoundCache::new(10);
+        let id = Ulid::new();
+        cache.insert(id, Arc::from(vec![0; 11]));
+        assert!(!cache.entries.contains(&id));
+        assert_eq!(cache.stats().size, 0);
+    }
+
+    #[test]
+    fn counters() {
+        let mut cache = SoundCache::new(10);
+        let id = Ulid::new();
+        assert!(cache.get(&id).is_none());
+        cache.insert(id, Arc::from(vec![0; 2]));
+        assert!(cache.get(&id).is_some());
+        assert!(cache.get(&id).is_some());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+    }
+}
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use regex::Regex;
 use serenity::{
-    all::{Context, GuildId, ResolvedOption, ResolvedValue},
+    all::{ActionRowComponent, Context, GuildId, ModalInteraction, ResolvedOption, ResolvedValue},
     model::{application::CommandInteraction, channel::Attachment, user::User},
 };
 
@@ -13,9 +13,8 @@ fn find_option<'a>(command: &'a CommandInteraction, name: &str) -> Option<Resolv
             if option.name == name {
                 return Some(option.value);
             }
-            match option.value {
-                ResolvedValue::SubCommand(options) => return browse(options, name),
-                _ => (),
+            if let ResolvedValue::SubCommand(options) = option.value {
+                return browse(options, name);
             }
         }
         None
@@ -66,6 +65,12 @@ pub fn find_user_option<'a>(command: &'a CommandInteraction, name: &str) -> Opti
     }
 }
 
+/// User targeted by a user context menu command.
+pub fn find_target_user(command: &CommandInteraction) -> Option<&User> {
+    let id = command.data.target_id?.to_user_id();
+    command.data.resolved.users.get(&id)
+}
+
 pub fn find_attachment_option<'a>(
     command: &'a CommandInteraction,
     name: &str,
@@ -77,13 +82,17 @@ pub fn find_attachment_option<'a>(
 }
 
 pub fn find_emoji_option(command: &CommandInteraction, name: &str) -> Option<String> {
-    let option = find_string_option(command, name, None)?;
-    if emojis::get(option).is_some() {
-        Some(option.to_owned())
+    parse_emoji(find_string_option(command, name, None)?)
+}
+
+/// Keep a known emoji as is, or extract the first emoji of the text.
+pub fn parse_emoji(text: &str) -> Option<String> {
+    if emojis::get(text).is_some() {
+        Some(text.to_owned())
     } else {
         Regex::new(r#"\p{Emoji}"#)
             .expect("Invalid emoji regex")
-            .find(option)
+            .find(text)
             .map(|m| m.as_str().to_owned())
     }
 }
@@ -99,9 +108,53 @@ pub fn find_duration_option(
     }
 }
 
+/// Value of a modal text input, `None` if left empty.
+pub fn find_modal_value<'a>(modal: &'a ModalInteraction, id: &str) -> Option<&'a str> {
+    modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == id => input.value.as_deref(),
+            _ => None,
+        })
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
 pub async fn resolve_username(ctx: &Context, user: &User, guild: GuildId) -> String {
     user.nick_in(ctx, guild)
         .await
         .or_else(|| user.global_name.clone())
         .unwrap_or_else(|| user.name.clone())
 }
+
+/// Turn a sound name into a file name without extension, replacing the
+/// characters forbidden on common file systems.
+pub fn file_name(name: &str) -> String {
+    let sanitized = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let sanitized = sanitized.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if sanitized.is_empty() {
+        "sound".to_owned()
+    } else {
+        sanitized.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn file_name() {
+        assert_eq!(super::file_name("Oh là là"), "Oh là là");
+        assert_eq!(super::file_name("../a/b:c?"), "_a_b_c_");
+        assert_eq!(super::file_name(" .. "), "sound");
+    }
+}
@@ -0,0 +1,616 @@
+use std::{
+    collections::{HashMap, HashSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use itertools::Itertools;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{ButtonStyle, ChannelId, GuildId, Http, MessageId, ReactionType},
+    builder::{
+        CreateActionRow, CreateButton, CreateMessage, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption, EditMessage,
+    },
+};
+use tokio::{
+    fs,
+    sync::{Mutex, broadcast::error::RecvError},
+    time::{Instant, timeout_at},
+};
+
+use crate::{
+    ROWS_PER_MESSAGE, SOUNDS_PER_ROW,
+    button::SoundButton,
+    events::{Events, GuildEvent},
+    soundboard::Soundboard,
+    store::{GroupMetadata, SoundMetadata},
+};
+
+/// Changes are applied once the soundboard has been quiet for this long, so a
+/// burst of changes edits the messages only once.
+const UPDATE_DELAY: Duration = Duration::from_secs(2);
+const EMPTY_SOUNDBOARD: &str = "There is no sounds uploaded to this server... yet.";
+
+/// One message of the soundboard: the group name on its first message, then at
+/// most `ROWS_PER_MESSAGE` rows of buttons.
+pub struct BoardMessage {
+    pub content: Option<String>,
+    pub buttons: Vec<SoundButton>,
+}
+
+impl BoardMessage {
+    pub fn components(&self) -> Vec<CreateActionRow> {
+        self.buttons
+            .chunks(SOUNDS_PER_ROW)
+            .map(|row| CreateActionRow::Buttons(row.iter().map(SoundButton::create).collect()))
+            .collect()
+    }
+}
+
+/// Split the sounds of a guild, sorted by group, into messages, with optional
+/// random and latest buttons.
+pub fn layout(
+    sounds: Vec<(String, Vec<SoundMetadata>)>,
+    groups: &[GroupMetadata],
+    add_random: bool,
+    add_latest: bool,
+) -> Vec<BoardMessage> {
+    let mut sounds = sounds
+        .into_iter()
+        .map(|(g, sounds)| {
+            (
+                g,
+                sounds
+                    .into_iter()
+                    .map(SoundButton::Sound)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Add random and latest buttons.
+    let total_sounds = sounds
+        .iter()
+        .map(|(_g, sounds)| sounds.len())
+        .sum::<usize>();
+    let mut has_shortcuts_row = false;
+    if add_random && total_sounds >= 2 {
+        has_shortcuts_row = true;
+        sounds.push(("Shortcuts".to_owned(), vec![SoundButton::Random(None)]))
+    }
+    if add_latest && total_sounds >= 2 {
+        if has_shortcuts_row {
+            sounds.last_mut().unwrap().1.push(SoundButton::Latest);
+        } else {
+            has_shortcuts_row = true;
+            sounds.push(("Shortcuts".to_owned(), vec![SoundButton::Latest]));
+        }
+    }
+
+    let mut messages = Vec::new();
+    let groups_len = sounds.len();
+    for (i, (group, mut sounds)) in sounds.into_iter().enumerate() {
+        // Add random button if enough sounds in group.
+        if add_random && sounds.len() >= 2 && (has_shortcuts_row && i != groups_len - 1) {
+            sounds.insert(0, SoundButton::Random(Some(group.clone())));
+        }
+
+        // Send the group header once then at most 5 sound rows per message.
+        let mut content = Some(
+            groups
+                .iter()
+                .find(|g| g.name == group)
+                .map(GroupMetadata::header)
+                .unwrap_or_else(|| format!("# {group}")),
+        );
+        let mut sounds = sounds.into_iter().peekable();
+        while sounds.peek().is_some() {
+            messages.push(BoardMessage {
+                content: content.take(),
+                buttons: sounds
+                    .by_ref()
+                    .take(ROWS_PER_MESSAGE * SOUNDS_PER_ROW)
+                    .collect(),
+            });
+        }
+    }
+    messages
+}
+
+/// Sound rows of a page, leaving room for the navigation buttons and the group
+/// menu.
+const ROWS_PER_PAGE: usize = ROWS_PER_MESSAGE - 2;
+/// Discord limit of select menu options, one of them being all groups.
+const MAX_GROUP_OPTIONS: usize = 25;
+const MAX_OPTION_LABEL: usize = 100;
+const ALL_GROUPS: &str = "all";
+
+pub fn group_hash(group: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    group.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Filters of an ephemeral paginated listing. They are carried in the custom
+/// ids of its components, the search last as it may contain dashes.
+#[derive(PartialEq, Debug)]
+pub struct ListFilter {
+    pub group: Option<u64>,
+    pub search: String,
+}
+
+impl ListFilter {
+    fn page_id(&self, page: usize) -> String {
+        let group = match self.group {
+            Some(hash) => hash.to_string(),
+            None => ALL_GROUPS.to_owned(),
+        };
+        format!("list-page-{page}-{group}-{}", self.search)
+    }
+
+    fn group_menu_id(&self) -> String {
+        format!("list-group-{}", self.search)
+    }
+
+    /// Parse the filters and page of a previous or next button.
+    pub fn parse_page(custom_id: &str) -> Option<(Self, usize)> {
+        let mut parts = custom_id.strip_prefix("list-page-")?.splitn(3, '-');
+        let page = parts.next()?.parse().ok()?;
+        let group = parse_group(parts.next()?)?;
+        let search = parts.next()?.to_owned();
+        Some((Self { group, search }, page))
+    }
+
+    /// Parse the filters of the group menu, with the group picked.
+    pub fn parse_group_menu(custom_id: &str, value: &str) -> Option<Self> {
+        Some(Self {
+            group: parse_group(value)?,
+            search: custom_id.strip_prefix("list-group-")?.to_owned(),
+        })
+    }
+}
+
+fn parse_group(group: &str) -> Option<Option<u64>> {
+    if group == ALL_GROUPS {
+        Some(None)
+    } else {
+        group.parse().ok().map(Some)
+    }
+}
+
+/// One page of an ephemeral listing.
+pub struct ListPage {
+    pub content: String,
+    pub components: Vec<CreateActionRow>,
+}
+
+/// Build a page of the sounds matching the search, by group. The group menu
+/// offers every group of these sounds, while the buttons only show the
+/// filtered group.
+pub fn list_page(
+    sounds: Vec<(String, Vec<SoundMetadata>)>,
+    filter: &ListFilter,
+    page: usize,
+) -> ListPage {
+    let mut group_menu = None;
+    if !sounds.is_empty() {
+        let options = std::iter::once(
+            CreateSelectMenuOption::new("All groups", ALL_GROUPS)
+                .default_selection(filter.group.is_none()),
+        )
+        .chain(sounds.iter().map(|(group, _)| {
+            let hash = group_hash(group);
+            CreateSelectMenuOption::new(
+                group.chars().take(MAX_OPTION_LABEL).collect::<String>(),
+                hash.to_string(),
+            )
+            .default_selection(filter.group == Some(hash))
+        }))
+        .take(MAX_GROUP_OPTIONS)
+        .collect();
+        group_menu = Some(CreateActionRow::SelectMenu(CreateSelectMenu::new(
+            filter.group_menu_id(),
+            CreateSelectMenuKind::String { options },
+        )));
+    }
+
+    let sounds = sounds
+        .into_iter()
+        .filter(|(group, _)| filter.group.is_none_or(|hash| group_hash(group) == hash))
+        .flat_map(|(_, sounds)| sounds)
+        .collect::<Vec<_>>();
+    if sounds.is_empty() {
+        return ListPage {
+            content: "No sounds match these filters.".to_owned(),
+            components: group_menu.into_iter().collect(),
+        };
+    }
+
+    let per_page = ROWS_PER_PAGE * SOUNDS_PER_ROW;
+    let pages = sounds.len().div_ceil(per_page);
+    let page = page.min(pages - 1);
+    let shown = sounds
+        .into_iter()
+        .skip(page * per_page)
+        .take(per_page)
+        .map(SoundButton::Sound)
+        .collect::<Vec<_>>();
+    let groups = shown
+        .iter()
+        .filter_map(|button| match button {
+            SoundButton::Sound(sound) => Some(sound.group.as_str()),
+            _ => None,
+        })
+        .dedup()
+        .join(", ");
+
+    let mut components = BoardMessage {
+        content: None,
+        buttons: shown,
+    }
+    .components();
+    components.push(CreateActionRow::Buttons(vec![
+        CreateButton::new(filter.page_id(page.saturating_sub(1)))
+            .style(ButtonStyle::Secondary)
+            .emoji(ReactionType::from('◀'))
+            .disabled(page == 0),
+        CreateButton::new("list-current")
+            .style(ButtonStyle::Secondary)
+            .label(format!("{}/{pages}", page + 1))
+            .disabled(true),
+        CreateButton::new(filter.page_id(page + 1))
+            .style(ButtonStyle::Secondary)
+            .emoji(ReactionType::from('▶'))
+            .disabled(page + 1 == pages),
+    ]));
+    components.extend(group_menu);
+
+    ListPage {
+        content: format!("**{groups}**"),
+        components,
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Dashboard {
+    channel: ChannelId,
+    messages: Vec<MessageId>,
+    random: bool,
+    latest: bool,
+}
+
+/// Soundboard messages kept up to date, at most one per guild, saved to a JSON
+/// file to survive restarts.
+pub struct Dashboards {
+    path: PathBuf,
+    dashboards: Mutex<HashMap<GuildId, Dashboard>>,
+}
+
+impl Dashboards {
+    pub async fn load(path: PathBuf) -> Self {
+        let dashboards = match fs::read(&path).await {
+            Ok(file) => serde_json::from_slice(&file).unwrap_or_else(|err| {
+                warn!("invalid dashboards file, starting without dashboards: {err}");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        info!("loaded {} soundboard dashboards", dashboards.len());
+        Self {
+            path,
+            dashboards: Mutex::new(dashboards),
+        }
+    }
+
+    /// Post a new dashboard in a channel, replacing the previous one of the
+    /// guild.
+    pub async fn post(
+        &self,
+        http: &Http,
+        soundboard: &Soundboard,
+        guild: GuildId,
+        channel: ChannelId,
+        random: bool,
+        latest: bool,
+    ) -> serenity::Result<()> {
+        let mut dashboards = self.dashboards.lock().await;
+        if let Some(previous) = dashboards.remove(&guild) {
+            delete_messages(http, &previous).await;
+        }
+
+        let mut dashboard = Dashboard {
+            channel,
+            messages: Vec::new(),
+            random,
+            latest,
+        };
+        let messages = board(soundboard, guild, random, latest).await;
+        let sent = send_messages(http, &mut dashboard, &messages).await;
+        dashboards.insert(guild, dashboard);
+        self.save(&dashboards).await;
+        sent
+    }
+
+    /// Update the dashboards whenever their sounds change.
+    pub fn update_loop(
+        self: Arc<Self>,
+        http: Arc<Http>,
+        soundboard: Arc<Soundboard>,
+        events: &Events,
+    ) {
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let mut changed = HashSet::new();
+                let mut deadline = None;
+                loop {
+                    let event = match deadline {
+                        None => receiver.recv().await,
+                        Some(deadline) => match timeout_at(deadline, receiver.recv()).await {
+                            Ok(event) => event,
+                            Err(_) => break,
+                        },
+                    };
+                    match event {
+                        Ok((
+                            guild,
+                            GuildEvent::SoundAdded { .. }
+                            | GuildEvent::SoundRemoved { .. }
+                            | GuildEvent::SoundUpdated { .. }
+                            | GuildEvent::GroupUpdated { .. },
+                        )) => {
+                            changed.insert(guild);
+                        }
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(missed)) => {
+                            warn!("dashboards missed {missed} events, updating all of them");
+                            changed.extend(self.dashboards.lock().await.keys());
+                        }
+                        Err(RecvError::Closed) => return,
+                    }
+                    deadline = Some(Instant::now() + UPDATE_DELAY);
+                }
+
+                for guild in changed {
+                    self.update(&http, &soundboard, guild).await;
+                }
+            }
+        });
+    }
+
+    /// Edit the messages of a guild dashboard in place, adding or removing
+    /// messages at the end as needed. The whole dashboard is posted again if
+    /// one of its messages cannot be edited, e.g. because it was deleted.
+    async fn update(&self, http: &Http, soundboard: &Soundboard, guild: GuildId) {
+        let mut dashboards = self.dashboards.lock().await;
+        let Some(dashboard) = dashboards.get_mut(&guild) else {
+            return;
+        };
+        info!("updating soundboard dashboard of guild {guild}");
+        let messages = board(soundboard, guild, dashboard.random, dashboard.latest).await;
+
+        let mut edited = true;
+        for (id, message) in dashboard.messages.iter().zip(&messages) {
+            if let Err(err) = dashboard
+                .channel
+                .edit_message(
+                    http,
+                    *id,
+                    EditMessage::new()
+                        .content(message.content.clone().unwrap_or_default())
+                        .components(message.components()),
+                )
+                .await
+            {
+                warn!("failed to edit dashboard message {id} of guild {guild}: {err}");
+                edited = false;
+                break;
+            }
+        }
+
+        let result = if edited {
+            let kept = messages.len().min(dashboard.messages.len());
+            for id in dashboard.messages.split_off(kept) {
+                if let Err(err) = dashboard.channel.delete_message(http, id).await {
+                    warn!("failed to delete dashboard message {id} of guild {guild}: {err}");
+                }
+            }
+            send_messages(http, dashboard, &messages[kept..]).await
+        } else {
+            delete_messages(http, dashboard).await;
+            dashboard.messages.clear();
+            send_messages(http, dashboard, &messages).await
+        };
+        if let Err(err) = result {
+            warn!("failed to update dashboard of guild {guild}: {err}");
+        }
+        self.save(&dashboards).await;
+    }
+
+    async fn save(&self, dashboards: &HashMap<GuildId, Dashboard>) {
+        let data = serde_json::to_vec(dashboards).expect("Failed to serialize dashboards");
+        if let Err(err) = fs::write(&self.path, data).await {
+            warn!("failed to save soundboard dashboards: {err}");
+        }
+    }
+}
+
+/// Messages of a dashboard, a single one saying so if there are no sounds.
+async fn board(
+    soundboard: &Soundboard,
+    guild: GuildId,
+    random: bool,
+    latest: bool,
+) -> Vec<BoardMessage> {
+    let messages = layout(
+        soundboard.list(guild).await,
+        &soundboard.groups(guild).await,
+        random,
+        latest,
+    );
+    if messages.is_empty() {
+        vec![BoardMessage {
+            content: Some(EMPTY_SOUNDBOARD.to_owned()),
+            buttons: Vec::new(),
+        }]
+    } else {
+        messages
+    }
+}
+
+/// Send messages at the end of a dashboard.
+async fn send_messages(
+    http: &Http,
+    dashboard: &mut Dashboard,
+    messages: &[BoardMessage],
+) -> serenity::Result<()> {
+    for message in messages {
+        let mut create = CreateMessage::new().components(message.components());
+        if let Some(content) = &message.content {
+            create = create.content(content);
+        }
+        let message = dashboard.channel.send_message(http, create).await?;
+        dashboard.messages.push(message.id);
+    }
+    Ok(())
+}
+
+async fn delete_messages(http: &Http, dashboard: &Dashboard) {
+    for id in &dashboard.messages {
+        let _ = dashboard.channel.delete_message(http, *id).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::ButtonStyle;
+    use ulid::Ulid;
+
+    use super::{ListFilter, ROWS_PER_MESSAGE, ROWS_PER_PAGE, SOUNDS_PER_ROW, group_hash};
+    use crate::{
+        button::SoundButton,
+        store::{GroupMetadata, SoundMetadata},
+    };
+
+    fn sounds(group: &str, count: usize) -> (String, Vec<SoundMetadata>) {
+        let sounds = (0..count)
+            .map(|index| SoundMetadata {
+                guild: 1,
+                id: Ulid::new(),
+                file: Ulid::new(),
+                hash: None,
+                name: format!("{group}{index}"),
+                emoji: None,
+                color: ButtonStyle::Primary,
+                group: group.to_owned(),
+                index,
+            })
+            .collect();
+        (group.to_owned(), sounds)
+    }
+
+    #[test]
+    fn layout() {
+        let per_message = ROWS_PER_MESSAGE * SOUNDS_PER_ROW;
+        let messages = super::layout(
+            vec![sounds("a", per_message), sounds("b", 1)],
+            &[GroupMetadata {
+                emoji: Some("🐸".to_owned()),
+                description: Some("Frogs.".to_owned()),
+                ..GroupMetadata::new("b".to_owned())
+            }],
+            true,
+            true,
+        );
+
+        // The random button of the group pushes its last sound to a second
+        // message without title.
+        let contents = messages
+            .iter()
+            .map(|message| message.content.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            contents,
+            [
+                Some("# a"),
+                None,
+                Some("# 🐸 b\nFrogs."),
+                Some("# Shortcuts")
+            ]
+        );
+        assert!(matches!(
+            messages[0].buttons[0],
+            SoundButton::Random(Some(_))
+        ));
+        assert_eq!(messages[1].buttons.len(), 1);
+        assert!(matches!(
+            messages[3].buttons[..],
+            [SoundButton::Random(None), SoundButton::Latest]
+        ));
+
+        // No shortcuts nor random button for a single sound.
+        let messages = super::layout(vec![sounds("a", 1)], &[], true, true);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].buttons.len(), 1);
+    }
+
+    #[test]
+    fn list_filter_ids() {
+        let filter = ListFilter {
+            group: Some(group_hash("memes")),
+            search: "a-b".to_owned(),
+        };
+        assert_eq!(
+            ListFilter::parse_page(&filter.page_id(3)),
+            Some((filter, 3))
+        );
+
+        let filter = ListFilter {
+            group: None,
+            search: String::new(),
+        };
+        assert_eq!(
+            ListFilter::parse_page(&filter.page_id(0)),
+            Some((filter, 0))
+        );
+        assert_eq!(
+            ListFilter::parse_group_menu("list-group-a-b", &group_hash("x").to_string()),
+            Some(ListFilter {
+                group: Some(group_hash("x")),
+                search: "a-b".to_owned(),
+            })
+        );
+        assert_eq!(ListFilter::parse_page("list-page-x-all-"), None);
+    }
+
+    #[test]
+    fn list_page() {
+        let per_page = ROWS_PER_PAGE * SOUNDS_PER_ROW;
+        let filter = ListFilter {
+            group: None,
+            search: String::new(),
+        };
+
+        // Sound rows, navigation and group menu.
+        let page = super::list_page(vec![sounds("a", per_page), sounds("b", 1)], &filter, 0);
+        assert_eq!(page.content, "**a**");
+        assert_eq!(page.components.len(), ROWS_PER_PAGE + 2);
+
+        // Out of range pages show the last one.
+        let page = super::list_page(vec![sounds("a", per_page), sounds("b", 1)], &filter, 9);
+        assert_eq!(page.content, "**b**");
+        assert_eq!(page.components.len(), 3);
+
+        let filter = ListFilter {
+            group: Some(group_hash("c")),
+            search: String::new(),
+        };
+        let page = super::list_page(vec![sounds("a", 1)], &filter, 0);
+        assert_eq!(page.content, "No sounds match these filters.");
+        assert_eq!(page.components.len(), 1);
+    }
+}
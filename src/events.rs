@@ -0,0 +1,96 @@
+use serde::Serialize;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use ulid::Ulid;
+
+/// Events kept for subscribers lagging behind before they miss some.
+const EVENTS_CAPACITY: usize = 256;
+
+/// Something that happened in a guild, published to the live event streams.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GuildEvent {
+    SoundPlayed {
+        id: Ulid,
+        name: String,
+        user: Option<EventUser>,
+        source: PlaySource,
+    },
+    SoundAdded {
+        id: Ulid,
+        name: String,
+        group: String,
+    },
+    SoundRemoved {
+        id: Ulid,
+        name: String,
+        group: String,
+    },
+    /// Renamed, moved to another group, or its emoji or color changed.
+    SoundUpdated {
+        id: Ulid,
+        name: String,
+        group: String,
+    },
+    /// Moved in the group order, renamed or its settings changed.
+    GroupUpdated {
+        group: String,
+    },
+    VoiceJoined {
+        channel: ChannelId,
+        user: EventUser,
+    },
+    VoiceLeft {
+        channel: ChannelId,
+        user: EventUser,
+    },
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EventUser {
+    pub id: UserId,
+    pub name: String,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaySource {
+    Button,
+    Command,
+    Api,
+}
+
+impl PlaySource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PlaySource::Button => "button",
+            PlaySource::Command => "command",
+            PlaySource::Api => "api",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Events {
+    sender: Sender<(GuildId, GuildEvent)>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENTS_CAPACITY).0,
+        }
+    }
+}
+
+impl Events {
+    pub fn publish(&self, guild: GuildId, event: GuildEvent) {
+        // Nobody listening is fine.
+        let _ = self.sender.send((guild, event));
+    }
+
+    /// Receive the events of every guild published from now on.
+    pub fn subscribe(&self) -> Receiver<(GuildId, GuildEvent)> {
+        self.sender.subscribe()
+    }
+}
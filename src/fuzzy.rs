@@ -0,0 +1,87 @@
+/// How well a search matches a name, from worst to best.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Rank {
+    /// The searched characters appear in order.
+    Subsequence,
+    Substring,
+    /// The search starts one of the words of the name.
+    WordStart,
+    Prefix,
+}
+
+/// Rank a name against a search, ignoring case and accents. Every name
+/// matches an empty search.
+pub fn rank(search: &str, name: &str) -> Option<Rank> {
+    let search = fold(search);
+    let name = fold(name);
+
+    let mut found = false;
+    for (index, _) in name.match_indices(&search) {
+        if index == 0 {
+            return Some(Rank::Prefix);
+        }
+        if name[..index]
+            .chars()
+            .next_back()
+            .is_some_and(|c| !c.is_alphanumeric())
+        {
+            return Some(Rank::WordStart);
+        }
+        found = true;
+    }
+    if found {
+        return Some(Rank::Substring);
+    }
+
+    let mut chars = name.chars();
+    search
+        .chars()
+        .all(|searched| chars.any(|c| c == searched))
+        .then_some(Rank::Subsequence)
+}
+
+/// Lowercase and strip the accents of latin letters.
+fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => folded.push('a'),
+            'ç' => folded.push('c'),
+            'è' | 'é' | 'ê' | 'ë' => folded.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => folded.push('i'),
+            'ñ' => folded.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => folded.push('o'),
+            'ù' | 'ú' | 'û' | 'ü' => folded.push('u'),
+            'ý' | 'ÿ' => folded.push('y'),
+            'æ' => folded.push_str("ae"),
+            'œ' => folded.push_str("oe"),
+            'ß' => folded.push_str("ss"),
+            c => folded.push(c),
+        }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rank, rank};
+
+    #[test]
+    fn ranks() {
+        assert_eq!(rank("bon", "Bonjour"), Some(Rank::Prefix));
+        assert_eq!(rank("bon", "Oh bon"), Some(Rank::WordStart));
+        assert_eq!(rank("bon", "abandon"), Some(Rank::Subsequence));
+        assert_eq!(rank("nob", "abandon"), None);
+        assert_eq!(rank("ban", "abandon"), Some(Rank::Substring));
+        assert_eq!(rank("bdn", "abandon"), Some(Rank::Subsequence));
+        assert_eq!(rank("", "abandon"), Some(Rank::Prefix));
+        assert!(rank("bon", "Bonjour") > rank("bon", "abandon"));
+    }
+
+    #[test]
+    fn accents() {
+        assert_eq!(rank("eleve", "Élève"), Some(Rank::Prefix));
+        assert_eq!(rank("é", "cafe"), Some(Rank::Substring));
+        assert_eq!(rank("coeur", "Cœur"), Some(Rank::Prefix));
+    }
+}
@@ -43,6 +43,23 @@ impl History {
         ))
     }
 
+    /// Number of plays of each sound in the last minutes.
+    pub async fn play_counts(&self, guild: GuildId) -> HashMap<Ulid, u32> {
+        let counters = self.guild_counters.lock().await;
+        let Some(history) = counters.get(&guild) else {
+            return HashMap::new();
+        };
+        history
+            .logs
+            .iter()
+            .filter(|(_user, ts, _sound)| ts.elapsed() <= LOGS_DURATION)
+            .map(|(_user, _ts, sound)| *sound)
+            .counts()
+            .into_iter()
+            .map(|(sound, count)| (sound, count as u32))
+            .collect()
+    }
+
     pub async fn get_latest_played(&self, guild: GuildId, offset: usize) -> Option<Ulid> {
         self.guild_counters
             .lock()
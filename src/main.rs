@@ -3,6 +3,7 @@
 use std::{
     collections::{HashSet, VecDeque},
     io::{Cursor, Write},
+    iter,
     net::SocketAddr,
     process::ExitCode,
     sync::{
@@ -20,14 +21,19 @@ use log::{error, info, warn};
 use serenity::{
     Client,
     all::{
-        AutocompleteChoice, ButtonStyle, ChannelId, ChannelType, Command, CommandInteraction,
-        CommandOptionType, CommandType, ComponentInteraction, CreateAllowedMentions,
-        CreateAutocompleteResponse, CreateButton, CreateCommand, CreateCommandOption,
-        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, GuildId,
-        HttpError, Interaction, Mention, ReactionType, Ready, UserId, VoiceState,
+        Attachment, AutocompleteChoice, ButtonStyle, ChannelId, ChannelType, Command,
+        CommandInteraction, CommandOptionType, CommandType, ComponentInteraction,
+        ComponentInteractionDataKind, CreateAllowedMentions, CreateAutocompleteResponse,
+        CreateButton, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, GuildId, HttpError, InputTextStyle,
+        Interaction, Mention, Message, MessageId, ModalInteraction, ReactionType, Ready, UserId,
+        VoiceState,
     },
     async_trait,
-    builder::{CreateActionRow, CreateAttachment, CreateInteractionResponseFollowup},
+    builder::{
+        CreateActionRow, CreateAttachment, CreateInputText, CreateInteractionResponseFollowup,
+        CreateModal, EditInteractionResponse,
+    },
     cache::Cache,
     client::{Context, EventHandler},
     http::Http,
@@ -40,9 +46,12 @@ use serenity::{
 // Songbird, };
 use songbird::EventHandler as VoiceEventHandler;
 use songbird::{
-    CoreEvent, Event, EventContext, SerenityInit, Songbird, driver::DecodeMode, input::Input,
+    CoreEvent, Event, EventContext, SerenityInit, Songbird,
+    driver::DecodeMode,
+    input::{Input, RawAdapter},
 };
 use symphonia as _;
+use thiserror::Error as ThisError;
 use tokio::{
     net::TcpListener,
     sync::{Mutex, mpsc::UnboundedSender, oneshot},
@@ -51,29 +60,47 @@ use ulid::Ulid;
 use zip::{ZipWriter, write::FileOptions as ZipFileOptions};
 
 use crate::{
-    api::ApiState,
-    button::SoundButton,
+    api::{ApiSettings, ApiState},
+    auth::ApiTokens,
+    dashboard::{Dashboards, ListFilter},
+    events::{EventUser, Events, GuildEvent, PlaySource},
     history::History,
+    metrics::Metrics,
     options::Options,
+    playing::NowPlaying,
     recorder::{Recorder, RecorderAction},
-    soundboard::Soundboard,
+    soundboard::{SoundUpdate, Soundboard, SoundboardError},
+    store::SoundMetadata,
 };
 
 mod api;
+mod auth;
+mod bulk;
 mod button;
+mod cache;
 mod command;
+mod dashboard;
+mod events;
+mod fuzzy;
 mod history;
+mod metrics;
 mod options;
+mod playing;
 mod recorder;
 mod soundboard;
+mod store;
 mod wav;
 
 /// Max body size is 25MiB including other fields. We cut at 24MiB because
 /// calculating the rest of the body is too unreliable.
-const MAX_FILE_SIZE: usize = 24 * (1 << 20);
+pub const MAX_FILE_SIZE: usize = 24 * (1 << 20);
 const ROWS_PER_MESSAGE: usize = 5;
 const SOUNDS_PER_ROW: usize = 5;
 const AUTOCOMPLETE_MAX_CHOICES: usize = 25;
+const MAX_BUTTON_LABEL: u16 = 80;
+const MAX_GROUP_DESCRIPTION: u16 = 1000;
+/// Keeps the search within the 100 characters of the paginated list buttons ids.
+const MAX_LIST_SEARCH_LENGTH: u16 = 60;
 const MAX_ATTACHEMENTS_PER_MESSAGE: usize = 10;
 
 /// Invalid Emoji error.
@@ -88,6 +115,11 @@ struct Handler {
     recorder: Arc<Mutex<Recorder>>,
     soundboard: Arc<Soundboard>,
     history: Arc<History>,
+    now_playing: Arc<NowPlaying>,
+    events: Arc<Events>,
+    api_tokens: Arc<ApiTokens>,
+    web_url: String,
+    dashboards: Arc<Dashboards>,
 }
 
 #[async_trait]
@@ -104,7 +136,31 @@ impl EventHandler for Handler {
             return;
         };
 
-        if let Some(channel) = old.and_then(|vs| vs.channel_id) {
+        let old_channel = old.and_then(|vs| vs.channel_id);
+        if old_channel != new.channel_id {
+            let user = EventUser {
+                id: new.user_id,
+                name: new
+                    .member
+                    .as_ref()
+                    .map_or_else(|| new.user_id.to_string(), |m| m.display_name().to_owned()),
+            };
+            if let Some(channel) = old_channel {
+                self.events.publish(
+                    guild_id,
+                    GuildEvent::VoiceLeft {
+                        channel,
+                        user: user.clone(),
+                    },
+                );
+            }
+            if let Some(channel) = new.channel_id {
+                self.events
+                    .publish(guild_id, GuildEvent::VoiceJoined { channel, user });
+            }
+        }
+
+        if let Some(channel) = old_channel {
             self.disconnect_if_alone(&ctx, guild_id, channel).await;
         }
         if let Some(channel) = new.channel_id {
@@ -119,6 +175,7 @@ impl EventHandler for Handler {
             Interaction::Autocomplete(autocomplete) => {
                 self.dispatch_autocomplete(ctx, autocomplete).await
             }
+            Interaction::Modal(modal) => self.dispatch_modal(ctx, modal).await,
             _ => return,
         }
     }
@@ -167,6 +224,10 @@ impl Handler {
             // Common.
             "version" => self.version(ctx, command).await,
             "join" => self.join_voice(ctx, command).await,
+            "play" => self.play_command(ctx, command).await,
+            "Add to soundboard" => self.add_message_sound(ctx, command).await,
+            "Download recording" => self.download_recording(ctx, command).await,
+            "Download last phrases" => self.download_recording_chunks(ctx, command).await,
 
             // Recorder.
             "recorder" => match parse_subcommand(&command) {
@@ -181,16 +242,25 @@ impl Handler {
             // Soundboard.
             "soundboard" => match parse_subcommand(&command) {
                 Some("list") => self.list_sounds(ctx, command).await,
+                Some("dashboard") => self.soundboard_dashboard(ctx, command).await,
                 Some("upload") => self.upload_sound(ctx, command).await,
+                Some("upload-bulk") => self.upload_sounds(ctx, command).await,
                 Some("download") => self.download_sound(ctx, command).await,
                 Some("delete") => self.delete_sound(ctx, command).await,
+                Some("edit") => self.edit_sound(ctx, command).await,
                 Some("rename") => self.rename_sound(ctx, command).await,
                 Some("move") => self.move_sound(ctx, command).await,
+                Some("reorder") => self.reorder_sound(ctx, command).await,
+                Some("reorder-group") => self.reorder_group(ctx, command).await,
+                Some("edit-group") => self.edit_group(ctx, command).await,
+                Some("rename-group") => self.rename_group(ctx, command).await,
+                Some("delete-group") => self.delete_group(ctx, command).await,
                 Some("change-color") => self.change_sound_color(ctx, command).await,
                 Some("change-emoji") => self.change_sound_emoji(ctx, command).await,
                 Some("id") => self.sound_id(ctx, command).await,
                 Some("backup") => self.backup_sounds(ctx, command).await,
                 Some("logs") => self.soundboard_logs(ctx, command).await,
+                Some("web") => self.web_login(ctx, command).await,
                 _ => (),
             },
             _ => (),
@@ -202,6 +272,11 @@ impl Handler {
             return;
         };
 
+        if component.data.custom_id.starts_with("list-") {
+            self.change_list_page(ctx, component).await;
+            return;
+        }
+
         let sound = if component.data.custom_id.starts_with("random-") {
             let Ok(hash) = component
                 .data
@@ -238,14 +313,65 @@ impl Handler {
 
         let (defer, played) = tokio::join!(
             component.defer(&ctx),
-            play_sound(manager, &self.soundboard, guild, sound)
+            play_sound(manager, &self.soundboard, &self.now_playing, guild, sound)
         );
         defer.expect("Failed to defer sound play");
-        if !played {
+        if played.is_err() {
             return;
         }
 
-        self.history.register(guild, component.user.id, sound).await;
+        let name = match &component.member {
+            Some(member) => member.display_name(),
+            None => component.user.display_name(),
+        };
+        self.register_play(guild, component.user.id, name, sound, PlaySource::Button)
+            .await;
+    }
+
+    async fn dispatch_modal(&self, ctx: Context, modal: ModalInteraction) {
+        if let Some(message) = modal.data.custom_id.strip_prefix("add-sound-") {
+            let Ok(message) = message.parse::<u64>() else {
+                return;
+            };
+            self.submit_message_sound(ctx, &modal, MessageId::new(message))
+                .await;
+        } else if let Some(sound) = modal.data.custom_id.strip_prefix("edit-sound-") {
+            let Ok(sound) = Ulid::from_string(sound) else {
+                return;
+            };
+            self.submit_sound_edit(ctx, &modal, sound).await;
+        } else if let Some(group) = modal.data.custom_id.strip_prefix("edit-group-") {
+            let Ok(group) = group.parse::<u64>() else {
+                return;
+            };
+            self.submit_group_edit(ctx, &modal, group).await;
+        }
+    }
+
+    /// Count a sound played by a user in its history and publish it.
+    async fn register_play(
+        &self,
+        guild: GuildId,
+        user: UserId,
+        name: &str,
+        sound: Ulid,
+        source: PlaySource,
+    ) {
+        self.history.register(guild, user, sound).await;
+        if let Some(details) = self.soundboard.details(guild, sound).await {
+            self.events.publish(
+                guild,
+                GuildEvent::SoundPlayed {
+                    id: sound,
+                    name: details.metadata.name,
+                    user: Some(EventUser {
+                        id: user,
+                        name: name.to_owned(),
+                    }),
+                    source,
+                },
+            );
+        }
     }
 
     async fn dispatch_autocomplete(&self, ctx: Context, interaction: CommandInteraction) {
@@ -257,17 +383,22 @@ impl Handler {
             return;
         };
 
-        let matches = match autocomplete.name {
-            "sound" => {
-                self.soundboard
-                    .names_matching(guild, autocomplete.value, AUTOCOMPLETE_MAX_CHOICES)
-                    .await
-            }
-            "group" => {
-                self.soundboard
-                    .groups_matching(guild, autocomplete.value, AUTOCOMPLETE_MAX_CHOICES)
-                    .await
-            }
+        let plays = self.history.play_counts(guild).await;
+        let choices = match autocomplete.name {
+            "sound" => self
+                .soundboard
+                .sounds_matching(guild, autocomplete.value, &plays, AUTOCOMPLETE_MAX_CHOICES)
+                .await
+                .into_iter()
+                .map(|(label, id)| AutocompleteChoice::new(label, id.to_string()))
+                .collect(),
+            "group" => self
+                .soundboard
+                .groups_matching(guild, autocomplete.value, &plays, AUTOCOMPLETE_MAX_CHOICES)
+                .await
+                .into_iter()
+                .map(AutocompleteChoice::from)
+                .collect(),
             _ => return,
         };
 
@@ -275,8 +406,7 @@ impl Handler {
             .create_response(
                 ctx,
                 CreateInteractionResponse::Autocomplete(
-                    CreateAutocompleteResponse::new()
-                        .set_choices(matches.into_iter().map(AutocompleteChoice::from).collect()),
+                    CreateAutocompleteResponse::new().set_choices(choices),
                 ),
             )
             .await
@@ -295,6 +425,84 @@ impl Handler {
             .expect("Version response failure");
     }
 
+    async fn play_command(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(random) = command::find_boolean_option(&command, "random", Some(false)) else {
+            return;
+        };
+        let name = command::find_string_option(&command, "sound", None);
+        let group = command::find_string_option(&command, "group", None);
+
+        let sound = match (random, name, group) {
+            (true, _, Some(group)) => match self.soundboard.find_group(guild, group).await {
+                Ok(group) => self
+                    .soundboard
+                    .random_id_in_group(guild, dashboard::group_hash(&group))
+                    .await
+                    .ok_or_else(|| PlayError::SoundNotFound.to_string()),
+                Err(err) => Err(err.to_string()),
+            },
+            (true, _, None) => self
+                .soundboard
+                .random_id(guild)
+                .await
+                .ok_or_else(|| PlayError::SoundNotFound.to_string()),
+            (false, Some(name), group) => self
+                .soundboard
+                .get_id(guild, name, group)
+                .await
+                .map_err(|err| err.to_string()),
+            (false, None, _) => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .ephemeral(true)
+                                .content("Pick a sound or a random one."),
+                        ),
+                    )
+                    .await
+                    .expect("Cannot send play error message");
+                return;
+            }
+        };
+
+        let manager = songbird::get(&ctx)
+            .await
+            .expect("Failed to get songbird manager");
+        let (defer, played) = tokio::join!(command.defer_ephemeral(&ctx), async {
+            let sound = sound?;
+            play_sound(manager, &self.soundboard, &self.now_playing, guild, sound)
+                .await
+                .map_err(|err| err.to_string())?;
+            Ok::<_, String>(sound)
+        });
+        defer.expect("Failed to defer sound play");
+
+        let text = match played {
+            Ok(sound) => {
+                let name = match &command.member {
+                    Some(member) => member.display_name(),
+                    None => command.user.display_name(),
+                };
+                self.register_play(guild, command.user.id, name, sound, PlaySource::Command)
+                    .await;
+                match self.soundboard.details(guild, sound).await {
+                    Some(details) => format!("Playing **{}**.", details.metadata.name),
+                    None => "Playing.".to_owned(),
+                }
+            }
+            Err(err) => err,
+        };
+        command
+            .edit_response(&ctx, EditInteractionResponse::new().content(text))
+            .await
+            .expect("Cannot send play response");
+    }
+
     async fn get_whitelist(&self, ctx: Context, command: CommandInteraction) {
         let Some(guild) = command.guild_id else {
             return;
@@ -433,20 +641,32 @@ impl Handler {
         let Some(guild) = command.guild_id else {
             return;
         };
-        let Some(requested_user) = command::find_user_option(&command, "user") else {
+        let Some(requested_user) = command::find_user_option(&command, "user")
+            .or_else(|| command::find_target_user(&command))
+        else {
             return;
         };
 
         let (tx, rx) = oneshot::channel::<Option<VecDeque<i16>>>();
-        self.recorder
-            .lock()
-            .await
+        let mut recorder = self.recorder.lock().await;
+        if !recorder.is_whitelisted(requested_user.id) {
+            drop(recorder);
+            respond_ephemeral(
+                &ctx,
+                &command,
+                format!("{requested_user} is not in the recorder's whitelist."),
+            )
+            .await;
+            return;
+        }
+        recorder
             .get_guild_recorder(guild)
             .await
             .send(RecorderAction::GetVoiceData(requested_user.id, tx))
             .expect("Download request failure");
+        drop(recorder);
 
-        let username = command::resolve_username(&ctx, &requested_user, guild).await;
+        let username = command::resolve_username(&ctx, requested_user, guild).await;
         let data = rx.await.expect("Voice data fetching error");
         match data.map(Vec::from) {
             Some(data) => {
@@ -472,17 +692,12 @@ impl Handler {
                 }
             }
             None => {
-                command
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content(format!("No voice data found for {}.", requested_user))
-                                .allowed_mentions(CreateAllowedMentions::new()),
-                        ),
-                    )
-                    .await
-                    .expect("Download response failure");
+                respond_ephemeral(
+                    &ctx,
+                    &command,
+                    format!("No voice data found for {requested_user}."),
+                )
+                .await;
             }
         }
     }
@@ -491,7 +706,9 @@ impl Handler {
         let Some(guild) = command.guild_id else {
             return;
         };
-        let Some(requested_user) = command::find_user_option(&command, "user") else {
+        let Some(requested_user) = command::find_user_option(&command, "user")
+            .or_else(|| command::find_target_user(&command))
+        else {
             return;
         };
         let Some(count) = command::find_integer_option(
@@ -520,9 +737,18 @@ impl Handler {
         };
 
         let (tx, rx) = oneshot::channel::<Option<Vec<Vec<i16>>>>();
-        self.recorder
-            .lock()
-            .await
+        let mut recorder = self.recorder.lock().await;
+        if !recorder.is_whitelisted(requested_user.id) {
+            drop(recorder);
+            respond_ephemeral(
+                &ctx,
+                &command,
+                format!("{requested_user} is not in the recorder's whitelist."),
+            )
+            .await;
+            return;
+        }
+        recorder
             .get_guild_recorder(guild)
             .await
             .send(RecorderAction::GetVoiceDataChunks(
@@ -532,8 +758,9 @@ impl Handler {
                 tx,
             ))
             .expect("Download request failure");
+        drop(recorder);
 
-        let username = command::resolve_username(&ctx, &requested_user, guild).await;
+        let username = command::resolve_username(&ctx, requested_user, guild).await;
         let data = rx.await.expect("Voice data fetching error");
         match data {
             Some(data) => {
@@ -543,9 +770,9 @@ impl Handler {
                         .create_followup(
                             &ctx,
                             CreateInteractionResponseFollowup::new().add_files(
-                                chunks.into_iter().enumerate().map(|(i, chunk)| {
+                                chunks.iter().enumerate().map(|(i, chunk)| {
                                     CreateAttachment::bytes(
-                                        wav::package(&chunk),
+                                        wav::package(chunk),
                                         if data.len() > 1 {
                                             format!(
                                                 "{}-{}.wav",
@@ -564,17 +791,12 @@ impl Handler {
                 }
             }
             None => {
-                command
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content(format!("No voice data found for {}.", requested_user))
-                                .allowed_mentions(CreateAllowedMentions::new()),
-                        ),
-                    )
-                    .await
-                    .expect("Download response failure");
+                respond_ephemeral(
+                    &ctx,
+                    &command,
+                    format!("No voice data found for {requested_user}."),
+                )
+                .await;
             }
         }
     }
@@ -590,52 +812,64 @@ impl Handler {
         let Some(add_latest) = command::find_boolean_option(&command, "latest", Some(true)) else {
             return;
         };
+        let Some(paginated) = command::find_boolean_option(&command, "paginated", Some(false))
+        else {
+            return;
+        };
+        let search = command::find_string_option(&command, "search", None);
+        // Match the group like everywhere else, an unknown one lists nothing.
+        let group = match command::find_string_option(&command, "group", None) {
+            Some(group) => Some(
+                self.soundboard
+                    .find_group(guild, group)
+                    .await
+                    .unwrap_or_else(|_| group.to_owned()),
+            ),
+            None => None,
+        };
 
-        let sounds = self.soundboard.list(guild).await;
-        if sounds.is_empty() {
+        if paginated {
+            let filter = ListFilter {
+                group: group.as_deref().map(dashboard::group_hash),
+                search: search.unwrap_or_default().to_owned(),
+            };
+            let page =
+                dashboard::list_page(self.listed_sounds(guild, &filter.search).await, &filter, 0);
             command
                 .create_response(
                     &ctx,
                     CreateInteractionResponse::Message(
                         CreateInteractionResponseMessage::new()
-                            .content("There is no sounds uploaded to this server... yet."),
+                            .ephemeral(true)
+                            .content(page.content)
+                            .components(page.components),
                     ),
                 )
                 .await
-                .expect("Cannot send empty soundboard message");
+                .expect("Failed to send paginated sound list");
             return;
         }
 
-        let mut sounds = sounds
-            .into_iter()
-            .map(|(g, sounds)| {
-                (
-                    g,
-                    sounds
-                        .into_iter()
-                        .map(|s| SoundButton::Sound(s))
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .collect::<Vec<_>>();
-
-        // Add random and latest buttons.
-        let total_sounds = sounds
-            .iter()
-            .map(|(_g, sounds)| sounds.len())
-            .sum::<usize>();
-        let mut has_shortcuts_row = false;
-        if add_random && total_sounds >= 2 {
-            has_shortcuts_row = true;
-            sounds.push(("Shortcuts".to_owned(), vec![SoundButton::Random(None)]))
+        let mut sounds = self.listed_sounds(guild, search.unwrap_or_default()).await;
+        if let Some(group) = &group {
+            sounds.retain(|(g, _)| g == group);
         }
-        if add_latest && total_sounds >= 2 {
-            if has_shortcuts_row {
-                sounds.last_mut().unwrap().1.push(SoundButton::Latest);
+        if sounds.is_empty() {
+            let content = if group.is_some() || search.is_some() {
+                "No sounds match these filters."
             } else {
-                has_shortcuts_row = true;
-                sounds.push(("Shortcuts".to_owned(), vec![SoundButton::Latest]));
-            }
+                "There is no sounds uploaded to this server... yet."
+            };
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content(content),
+                    ),
+                )
+                .await
+                .expect("Cannot send empty soundboard message");
+            return;
         }
 
         command
@@ -647,41 +881,109 @@ impl Handler {
             .await
             .expect("Failed to delete original sound list interaction");
 
-        let groups_len = sounds.len();
-        for (i, (group, mut sounds)) in sounds.into_iter().enumerate() {
-            // Add random button if enough sounds in group.
-            if add_random && sounds.len() >= 2 && (has_shortcuts_row && i != groups_len - 1) {
-                sounds.insert(0, SoundButton::Random(Some(group.clone())));
+        let groups = self.soundboard.groups(guild).await;
+        for message in dashboard::layout(sounds, &groups, add_random, add_latest) {
+            let mut create = CreateMessage::new().components(message.components());
+            if let Some(content) = message.content {
+                create = create.content(content);
             }
+            command
+                .channel_id
+                .send_message(&ctx, create)
+                .await
+                .expect("Failed to send sounds list");
+        }
+    }
 
-            // Send the group name once then at most 5 sound rows per message.
-            for (message_index, sounds_message) in
-                sounds.chunks(ROWS_PER_MESSAGE * SOUNDS_PER_ROW).enumerate()
-            {
-                command
-                    .channel_id
-                    .send_message(&ctx, {
-                        let mut message = CreateMessage::new();
-                        if message_index == 0 {
-                            message = message.content(format!("# {group}"));
-                        }
-                        message.components(
-                            sounds_message
-                                .chunks(SOUNDS_PER_ROW)
-                                .map(|sounds_row| {
-                                    CreateActionRow::Buttons(
-                                        sounds_row
-                                            .into_iter()
-                                            .map(|button| button.create())
-                                            .collect(),
-                                    )
-                                })
-                                .collect(),
-                        )
-                    })
-                    .await
-                    .expect("Failed to send sounds list");
-            }
+    /// Sounds whose name contains the search, all of them if empty.
+    async fn listed_sounds(
+        &self,
+        guild: GuildId,
+        search: &str,
+    ) -> Vec<(String, Vec<SoundMetadata>)> {
+        let search = (!search.is_empty()).then_some(search);
+        self.soundboard
+            .search(guild, search, None)
+            .await
+            .into_iter()
+            .map(|(group, sounds)| {
+                (
+                    group,
+                    sounds.into_iter().map(|sound| sound.metadata).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Switch the page or the group of an ephemeral sound list.
+    async fn change_list_page(&self, ctx: Context, component: ComponentInteraction) {
+        let Some(guild) = component.guild_id else {
+            return;
+        };
+
+        let parsed = match &component.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values
+                .first()
+                .and_then(|value| ListFilter::parse_group_menu(&component.data.custom_id, value))
+                .map(|filter| (filter, 0)),
+            _ => ListFilter::parse_page(&component.data.custom_id),
+        };
+        let Some((filter, page)) = parsed else {
+            return;
+        };
+
+        let page = dashboard::list_page(
+            self.listed_sounds(guild, &filter.search).await,
+            &filter,
+            page,
+        );
+        component
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(page.content)
+                        .components(page.components),
+                ),
+            )
+            .await
+            .expect("Failed to update paginated sound list");
+    }
+
+    async fn soundboard_dashboard(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+
+        let Some(add_random) = command::find_boolean_option(&command, "random", Some(true)) else {
+            return;
+        };
+        let Some(add_latest) = command::find_boolean_option(&command, "latest", Some(true)) else {
+            return;
+        };
+
+        command
+            .defer(&ctx)
+            .await
+            .expect("Failed to defer soundboard dashboard");
+        command
+            .delete_response(&ctx)
+            .await
+            .expect("Failed to delete original soundboard dashboard interaction");
+
+        if let Err(err) = self
+            .dashboards
+            .post(
+                &ctx.http,
+                &self.soundboard,
+                guild,
+                command.channel_id,
+                add_random,
+                add_latest,
+            )
+            .await
+        {
+            warn!("failed to post soundboard dashboard of guild {guild}: {err}");
         }
     }
 
@@ -699,15 +1001,20 @@ impl Handler {
             return;
         };
         let emoji = command::find_emoji_option(&command, "emoji");
-        let color = command::find_string_option(&command, "color", None)
-            .map(button::parse_color)
-            .unwrap_or_else(|| button::determinist(&name.to_lowercase(), self.allow_grey));
+        let color = match command::find_string_option(&command, "color", None) {
+            Some(color) => button::parse_color(color),
+            None => {
+                self.soundboard
+                    .default_color(guild, group, name, self.allow_grey)
+                    .await
+            }
+        };
         let index =
             command::find_integer_option(&command, "position", None).map(|p| (p - 1) as usize);
 
         match self
             .soundboard
-            .add(
+            .add_attachment(
                 attachment,
                 guild,
                 name.to_owned(),
@@ -718,23 +1025,22 @@ impl Handler {
             )
             .await
         {
-            Ok(id) => {
+            Ok((id, duplicate)) => {
+                let mut message = CreateInteractionResponseMessage::new().components(vec![
+                    CreateActionRow::Buttons(vec![{
+                        let mut button = CreateButton::new(id.to_string()).label(name).style(color);
+                        if let Some(emoji) = emoji {
+                            button = button.emoji(ReactionType::Unicode(emoji));
+                        }
+                        button
+                    }]),
+                ]);
+                if let Some(duplicate) = duplicate {
+                    message =
+                        message.content(format!("⚠️ This sound is identical to **{duplicate}**."));
+                }
                 match command
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().components(vec![
-                                CreateActionRow::Buttons(vec![{
-                                    let mut button =
-                                        CreateButton::new(id.to_string()).label(name).style(color);
-                                    if let Some(emoji) = emoji {
-                                        button = button.emoji(ReactionType::Unicode(emoji));
-                                    }
-                                    button
-                                }]),
-                            ]),
-                        ),
-                    )
+                    .create_response(&ctx, CreateInteractionResponse::Message(message))
                     .await
                 {
                     Ok(()) => {}
@@ -744,38 +1050,22 @@ impl Handler {
                             .delete_id(guild, id)
                             .await
                             .expect("Failed to delete sound due to error");
-                        let err_msg = match err {
-                            SerenityError::Http(http_error) => match http_error {
-                                HttpError::UnsuccessfulRequest(resp) => {
-                                    if resp.status_code.as_u16() == StatusCode::BAD_REQUEST
-                                        && resp.error.code == INVALID_EMOJI_CODE
-                                        && resp.error.errors.into_iter().any(|sub_error| {
-                                            sub_error.code == INVALID_EMOJI_MESSAGE
-                                        })
-                                    {
-                                        command
-                                            .create_response(
-                                                &ctx,
-                                                CreateInteractionResponse::Message(
-                                                    CreateInteractionResponseMessage::new()
-                                                        .content("Invalid emoji."),
-                                                ),
-                                            )
-                                            .await
-                                            .expect("Cannot send sound creation emoji error");
-                                        "Uncaught invalid emoji".to_owned()
-                                    } else {
-                                        format!("Different status code: {}", resp.error.code)
-                                    }
-                                }
-                                err => err.to_string(),
-                            },
-                            err => err.to_string(),
-                        };
-                        warn!(
-                            "unexpected error while sending sound button for the first time: {:?}",
-                            err_msg
-                        );
+                        if is_invalid_emoji_error(&err) {
+                            command
+                                .create_response(
+                                    &ctx,
+                                    CreateInteractionResponse::Message(
+                                        CreateInteractionResponseMessage::new()
+                                            .content("Invalid emoji."),
+                                    ),
+                                )
+                                .await
+                                .expect("Cannot send sound creation emoji error");
+                        } else {
+                            warn!(
+                                "unexpected error while sending sound button for the first time: {err}"
+                            );
+                        }
                     }
                 }
             }
@@ -793,56 +1083,271 @@ impl Handler {
         }
     }
 
-    async fn download_sound(&self, ctx: Context, command: CommandInteraction) {
+    /// Upload the sounds of several attachments or of ZIP archives at once.
+    async fn upload_sounds(&self, ctx: Context, command: CommandInteraction) {
         let Some(guild) = command.guild_id else {
             return;
         };
-        let Some(name) = command::find_string_option(&command, "sound", None) else {
+        let attachments = iter::once("sounds".to_owned())
+            .chain((2..=bulk::MAX_BULK_ATTACHMENTS).map(|n| format!("sound-{n}")))
+            .filter_map(|name| command::find_attachment_option(&command, &name))
+            .collect::<Vec<_>>();
+        if attachments.is_empty() {
             return;
-        };
-        let group = command::find_string_option(&command, "group", None);
-
-        // Does not support splitting.
-        match self.soundboard.get_wav_by_name(guild, name, group).await {
-            Ok(data) if data.len() <= MAX_FILE_SIZE => {
-                command.defer(&ctx).await.expect("Download defer failed");
-                command
-                    .create_followup(
-                        &ctx,
-                        CreateInteractionResponseFollowup::new()
-                            .add_file(CreateAttachment::bytes(data, format!("{name}.wav"))),
-                    )
-                    .await
-                    .expect("Sound data transmission failure");
-            }
-            Ok(_) => {
-                command
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().content("Sound too large."),
-                        ),
-                    )
-                    .await
-                    .expect("Download response failure");
-            }
-            Err(err) => {
-                command
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new().content(err.to_string()),
-                        ),
-                    )
-                    .await
-                    .expect("Download response failure");
-            }
         }
-    }
+        let group = command::find_string_option(&command, "group", None);
 
-    async fn delete_sound(&self, ctx: Context, command: CommandInteraction) {
-        if !self.allow_delete {
-            return;
+        // Downloading and transcoding many sounds can take a while.
+        command
+            .defer(&ctx)
+            .await
+            .expect("Failed to defer bulk upload");
+        let (uploads, left_out) = self
+            .soundboard
+            .add_bulk(&attachments, guild, group, self.allow_grey)
+            .await;
+        command
+            .edit_response(
+                &ctx,
+                EditInteractionResponse::new().content(bulk::summary(&uploads, left_out)),
+            )
+            .await
+            .expect("Cannot send bulk upload summary");
+    }
+
+    /// Ask for the details of the sound to create from the audio of a message.
+    async fn add_message_sound(&self, ctx: Context, command: CommandInteraction) {
+        let Some(message) = command
+            .data
+            .target_id
+            .and_then(|id| command.data.resolved.messages.get(&id.to_message_id()))
+        else {
+            return;
+        };
+
+        let Some(attachment) = audio_attachment(message) else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content("This message has no audio attachment."),
+                    ),
+                )
+                .await
+                .expect("Cannot send missing audio message");
+            return;
+        };
+        let name = attachment
+            .filename
+            .rsplit_once('.')
+            .map_or(attachment.filename.as_str(), |(stem, _)| stem)
+            .chars()
+            .take(MAX_BUTTON_LABEL as usize)
+            .collect::<String>();
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Modal(
+                    CreateModal::new(format!("add-sound-{}", message.id), "Add to soundboard")
+                        .components(vec![
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Name", "name")
+                                    .value(name)
+                                    .max_length(MAX_BUTTON_LABEL),
+                            ),
+                            CreateActionRow::InputText(CreateInputText::new(
+                                InputTextStyle::Short,
+                                "Group",
+                                "group",
+                            )),
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Emoji", "emoji")
+                                    .required(false),
+                            ),
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Color", "color")
+                                    .placeholder("blue, green, red or grey")
+                                    .required(false),
+                            ),
+                        ]),
+                ),
+            )
+            .await
+            .expect("Cannot send add sound modal");
+    }
+
+    /// Create a sound from the audio of a message once its details are
+    /// submitted. The message is fetched again as attachment URLs expire.
+    async fn submit_message_sound(
+        &self,
+        ctx: Context,
+        modal: &ModalInteraction,
+        message: MessageId,
+    ) {
+        let Some(guild) = modal.guild_id else {
+            return;
+        };
+        let (Some(name), Some(group)) = (
+            command::find_modal_value(modal, "name"),
+            command::find_modal_value(modal, "group"),
+        ) else {
+            respond_modal_ephemeral(&ctx, modal, "Name and group cannot be empty.").await;
+            return;
+        };
+        let emoji = command::find_modal_value(modal, "emoji").and_then(command::parse_emoji);
+        let color = match command::find_modal_value(modal, "color") {
+            Some(color) => button::parse_color(&color.to_lowercase()),
+            None => {
+                self.soundboard
+                    .default_color(guild, group, name, self.allow_grey)
+                    .await
+            }
+        };
+
+        modal
+            .defer(&ctx)
+            .await
+            .expect("Failed to defer sound creation");
+
+        let message = match modal.channel_id.message(&ctx, message).await {
+            Ok(message) => Some(message),
+            Err(err) => {
+                warn!("failed to fetch message {message} to add its sound: {err}");
+                None
+            }
+        };
+        let Some(attachment) = message.as_ref().and_then(audio_attachment) else {
+            modal
+                .edit_response(
+                    &ctx,
+                    EditInteractionResponse::new().content("Cannot find that audio anymore."),
+                )
+                .await
+                .expect("Cannot send sound creation error message");
+            return;
+        };
+
+        match self
+            .soundboard
+            .add_attachment(
+                attachment,
+                guild,
+                name.to_owned(),
+                emoji.clone(),
+                color,
+                group.to_owned(),
+                None,
+            )
+            .await
+        {
+            Ok((id, duplicate)) => {
+                let mut response =
+                    EditInteractionResponse::new().components(vec![CreateActionRow::Buttons(
+                        vec![{
+                            let mut button =
+                                CreateButton::new(id.to_string()).label(name).style(color);
+                            if let Some(emoji) = emoji {
+                                button = button.emoji(ReactionType::Unicode(emoji));
+                            }
+                            button
+                        }],
+                    )]);
+                if let Some(duplicate) = duplicate {
+                    response =
+                        response.content(format!("⚠️ This sound is identical to **{duplicate}**."));
+                }
+                if let Err(err) = modal.edit_response(&ctx, response).await {
+                    // Rollback creation, most likely because of an invalid emoji.
+                    self.soundboard
+                        .delete_id(guild, id)
+                        .await
+                        .expect("Failed to delete sound due to error");
+                    let content = if is_invalid_emoji_error(&err) {
+                        "Invalid emoji."
+                    } else {
+                        warn!(
+                            "unexpected error while sending sound button for the first time: {err}"
+                        );
+                        "Failed to add the sound."
+                    };
+                    modal
+                        .edit_response(
+                            &ctx,
+                            EditInteractionResponse::new()
+                                .content(content)
+                                .components(Vec::new()),
+                        )
+                        .await
+                        .expect("Cannot send sound creation error message");
+                }
+            }
+            Err(err) => {
+                modal
+                    .edit_response(
+                        &ctx,
+                        EditInteractionResponse::new().content(err.to_string()),
+                    )
+                    .await
+                    .expect("Cannot send sound creation error message");
+            }
+        }
+    }
+
+    async fn download_sound(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return;
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        // Does not support splitting.
+        match self.soundboard.get_wav_by_name(guild, name, group).await {
+            Ok((name, data)) if data.len() <= MAX_FILE_SIZE => {
+                command.defer(&ctx).await.expect("Download defer failed");
+                command
+                    .create_followup(
+                        &ctx,
+                        CreateInteractionResponseFollowup::new().add_file(CreateAttachment::bytes(
+                            data,
+                            format!("{}.wav", command::file_name(&name)),
+                        )),
+                    )
+                    .await
+                    .expect("Sound data transmission failure");
+            }
+            Ok(_) => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new().content("Sound too large."),
+                        ),
+                    )
+                    .await
+                    .expect("Download response failure");
+            }
+            Err(err) => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new().content(err.to_string()),
+                        ),
+                    )
+                    .await
+                    .expect("Download response failure");
+            }
+        }
+    }
+
+    async fn delete_sound(&self, ctx: Context, command: CommandInteraction) {
+        if !self.allow_delete {
+            return;
         }
         let Some(guild) = command.guild_id else {
             return;
@@ -867,25 +1372,399 @@ impl Handler {
             .expect("Cannot send sound deletion error message");
     }
 
+    /// Open a dialog to change every detail of a sound at once.
+    async fn edit_sound(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return;
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let details = match self.soundboard.get_id(guild, name, group).await {
+            Ok(id) => self.soundboard.details(guild, id).await,
+            Err(err) => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new().content(err.to_string()),
+                        ),
+                    )
+                    .await
+                    .expect("Cannot send sound edition error message");
+                return;
+            }
+        };
+        let Some(sound) = details.map(|details| details.metadata) else {
+            return;
+        };
+        let position = self
+            .soundboard
+            .list(guild)
+            .await
+            .into_iter()
+            .find(|(group, _)| *group == sound.group)
+            .and_then(|(_, sounds)| sounds.iter().position(|s| s.id == sound.id))
+            .unwrap_or_default();
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Modal(
+                    CreateModal::new(format!("edit-sound-{}", sound.id), "Edit sound").components(
+                        vec![
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Name", "name")
+                                    .value(&sound.name)
+                                    .max_length(MAX_BUTTON_LABEL),
+                            ),
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Group", "group")
+                                    .value(&sound.group),
+                            ),
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Emoji", "emoji")
+                                    .value(sound.emoji.clone().unwrap_or_default())
+                                    .placeholder("Leave empty to remove it")
+                                    .required(false),
+                            ),
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Color", "color")
+                                    .value(button::as_str(sound.color))
+                                    .placeholder("blue, green, red or grey"),
+                            ),
+                            CreateActionRow::InputText(
+                                CreateInputText::new(
+                                    InputTextStyle::Short,
+                                    "Position in the group",
+                                    "position",
+                                )
+                                .value((position + 1).to_string()),
+                            ),
+                        ],
+                    ),
+                ),
+            )
+            .await
+            .expect("Cannot send sound edition modal");
+    }
+
+    async fn submit_sound_edit(&self, ctx: Context, modal: &ModalInteraction, sound: Ulid) {
+        let Some(guild) = modal.guild_id else {
+            return;
+        };
+        let (Some(name), Some(group)) = (
+            command::find_modal_value(modal, "name"),
+            command::find_modal_value(modal, "group"),
+        ) else {
+            respond_modal_ephemeral(&ctx, modal, "Name and group cannot be empty.").await;
+            return;
+        };
+
+        let update = match (
+            command::find_modal_value(modal, "emoji").map(command::parse_emoji),
+            command::find_modal_value(modal, "color")
+                .and_then(|color| button::try_parse_color(&color.to_lowercase())),
+            command::find_modal_value(modal, "position")
+                .and_then(|position| position.parse::<usize>().ok())
+                .filter(|p| *p >= 1),
+        ) {
+            (Some(None), _, _) => Err("Invalid emoji.".to_owned()),
+            (_, None, _) => Err("Invalid color.".to_owned()),
+            (_, _, None) => Err("Invalid position.".to_owned()),
+            (emoji, Some(color), Some(position)) => Ok(SoundUpdate {
+                name: Some(name.to_owned()),
+                group: Some(group.to_owned()),
+                emoji: Some(emoji.flatten()),
+                color: Some(color),
+                position: Some(position - 1),
+            }),
+        };
+        let text = match update {
+            Ok(update) => match self.soundboard.update(guild, sound, update).await {
+                Ok((_, true)) => "Sound updated.".to_owned(),
+                Ok((_, false)) => "Nothing changed.".to_owned(),
+                Err(err) => err.to_string(),
+            },
+            Err(err) => err,
+        };
+        modal
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await
+            .expect("Cannot send sound edition response");
+    }
+
     async fn rename_sound(&self, ctx: Context, command: CommandInteraction) {
         let Some(guild) = command.guild_id else {
             return;
         };
-        let Some(name) = command::find_string_option(&command, "sound", None) else {
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return;
+        };
+        let Some(new_name) = command::find_string_option(&command, "new-name", None) else {
+            return;
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let text = match self
+            .soundboard
+            .rename(guild, name, group, new_name.to_owned())
+            .await
+        {
+            Ok(true) => "Sound's name changed.".to_owned(),
+            Ok(false) => "The sound already had this name.".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await
+            .expect("Cannot send sound's name change error message");
+    }
+
+    async fn move_sound(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return;
+        };
+        let Some(new_name) = command::find_string_option(&command, "new-group", None) else {
+            return;
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let text = match self
+            .soundboard
+            .move_group(guild, name, group, new_name.to_owned())
+            .await
+        {
+            Ok(true) => "Sound's group changed.".to_owned(),
+            Ok(false) => "This sound already was in this group.".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await
+            .expect("Cannot send sound's group change error message");
+    }
+
+    async fn reorder_sound(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return;
+        };
+        let Some(position) = command::find_integer_option(&command, "position", None) else {
+            return;
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let update = SoundUpdate {
+            position: Some((position - 1) as usize),
+            ..SoundUpdate::default()
+        };
+        let moved = match self.soundboard.get_id(guild, name, group).await {
+            Ok(id) => self.soundboard.update(guild, id, update).await,
+            Err(err) => Err(err),
+        };
+        let text = match moved {
+            Ok((_, true)) => "Sound moved.".to_owned(),
+            Ok((_, false)) => "The sound already was at this position.".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await
+            .expect("Cannot send sound reorder error message");
+    }
+
+    async fn reorder_group(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(group) = command::find_string_option(&command, "group", None) else {
+            return;
+        };
+        let Some(position) = command::find_integer_option(&command, "position", None) else {
+            return;
+        };
+
+        let text = match self
+            .soundboard
+            .reorder_group(guild, group, (position - 1) as usize)
+            .await
+        {
+            Ok(true) => "Group moved.".to_owned(),
+            Ok(false) => "The group already was at this position.".to_owned(),
+            Err(err) => err.to_string(),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await
+            .expect("Cannot send group reorder error message");
+    }
+
+    /// Open a dialog to change the default color, emoji and description of a
+    /// group.
+    async fn edit_group(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(group) = command::find_string_option(&command, "group", None) else {
+            return;
+        };
+
+        let groups = self.soundboard.groups(guild).await;
+        let Some(group) = groups
+            .into_iter()
+            .find(|g| g.name.to_lowercase() == group.to_lowercase())
+        else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(SoundboardError::GroupNotFound.to_string()),
+                    ),
+                )
+                .await
+                .expect("Cannot send group edition error message");
+            return;
+        };
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Modal(
+                    CreateModal::new(
+                        format!("edit-group-{}", dashboard::group_hash(&group.name)),
+                        "Edit group",
+                    )
+                    .components(vec![
+                        CreateActionRow::InputText(
+                            CreateInputText::new(
+                                InputTextStyle::Short,
+                                "Default color of new sounds",
+                                "color",
+                            )
+                            .value(group.color.map(button::as_str).unwrap_or_default())
+                            .placeholder("blue, green, red or grey, empty to pick from names")
+                            .required(false),
+                        ),
+                        CreateActionRow::InputText(
+                            CreateInputText::new(InputTextStyle::Short, "Header emoji", "emoji")
+                                .value(group.emoji.unwrap_or_default())
+                                .placeholder("Leave empty to remove it")
+                                .required(false),
+                        ),
+                        CreateActionRow::InputText(
+                            CreateInputText::new(
+                                InputTextStyle::Paragraph,
+                                "Description",
+                                "description",
+                            )
+                            .value(group.description.unwrap_or_default())
+                            .max_length(MAX_GROUP_DESCRIPTION)
+                            .required(false),
+                        ),
+                    ]),
+                ),
+            )
+            .await
+            .expect("Cannot send group edition modal");
+    }
+
+    async fn submit_group_edit(&self, ctx: Context, modal: &ModalInteraction, group: u64) {
+        let Some(guild) = modal.guild_id else {
+            return;
+        };
+        let Some(group) = self
+            .soundboard
+            .groups(guild)
+            .await
+            .into_iter()
+            .find(|g| dashboard::group_hash(&g.name) == group)
+        else {
+            return;
+        };
+
+        let color = command::find_modal_value(modal, "color")
+            .map(|color| button::try_parse_color(&color.to_lowercase()).ok_or(()))
+            .transpose();
+        let emoji = command::find_modal_value(modal, "emoji")
+            .map(|emoji| command::parse_emoji(emoji).ok_or(()))
+            .transpose();
+        let description = command::find_modal_value(modal, "description").map(str::to_owned);
+        let text = match (color, emoji) {
+            (Err(()), _) => "Invalid color.".to_owned(),
+            (_, Err(())) => "Invalid emoji.".to_owned(),
+            (Ok(color), Ok(emoji)) => match self
+                .soundboard
+                .change_group_settings(guild, &group.name, color, emoji, description)
+                .await
+            {
+                Ok(true) => "Group updated.".to_owned(),
+                Ok(false) => "Nothing changed.".to_owned(),
+                Err(err) => err.to_string(),
+            },
+        };
+        modal
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await
+            .expect("Cannot send group edition response");
+    }
+
+    async fn rename_group(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+        let Some(group) = command::find_string_option(&command, "group", None) else {
             return;
         };
         let Some(new_name) = command::find_string_option(&command, "new-name", None) else {
             return;
         };
-        let group = command::find_string_option(&command, "group", None);
 
         let text = match self
             .soundboard
-            .rename(guild, name, group, new_name.to_owned())
+            .rename_group(guild, group, new_name.to_owned())
             .await
         {
-            Ok(true) => "Sound's name changed.".to_owned(),
-            Ok(false) => "The sound already had this name.".to_owned(),
+            Ok(true) => "Group's name changed.".to_owned(),
+            Ok(false) => "The group already had this name.".to_owned(),
             Err(err) => err.to_string(),
         };
         command
@@ -896,28 +1775,23 @@ impl Handler {
                 ),
             )
             .await
-            .expect("Cannot send sound's name change error message");
+            .expect("Cannot send group's name change error message");
     }
 
-    async fn move_sound(&self, ctx: Context, command: CommandInteraction) {
-        let Some(guild) = command.guild_id else {
+    async fn delete_group(&self, ctx: Context, command: CommandInteraction) {
+        if !self.allow_delete {
             return;
-        };
-        let Some(name) = command::find_string_option(&command, "sound", None) else {
+        }
+        let Some(guild) = command.guild_id else {
             return;
         };
-        let Some(new_name) = command::find_string_option(&command, "new-group", None) else {
+        let Some(group) = command::find_string_option(&command, "group", None) else {
             return;
         };
-        let group = command::find_string_option(&command, "group", None);
 
-        let text = match self
-            .soundboard
-            .move_group(guild, name, group, new_name.to_owned())
-            .await
-        {
-            Ok(true) => "Sound's group changed.".to_owned(),
-            Ok(false) => "This sound already was in this group.".to_owned(),
+        let text = match self.soundboard.delete_group(guild, group).await {
+            Ok(1) => "Deleted with its only sound. *(for ever)*".to_owned(),
+            Ok(count) => format!("Deleted with its {count} sounds. *(for ever)*"),
             Err(err) => err.to_string(),
         };
         command
@@ -928,7 +1802,7 @@ impl Handler {
                 ),
             )
             .await
-            .expect("Cannot send sound's group change error message");
+            .expect("Cannot send group deletion error message");
     }
 
     async fn change_sound_color(&self, ctx: Context, command: CommandInteraction) {
@@ -1021,6 +1895,32 @@ impl Handler {
             .expect("Cannot send sound ID error message");
     }
 
+    async fn web_login(&self, ctx: Context, command: CommandInteraction) {
+        let Some(guild) = command.guild_id else {
+            return;
+        };
+
+        let name = match &command.member {
+            Some(member) => member.display_name(),
+            None => command.user.display_name(),
+        };
+        let token = self.api_tokens.create_web_session(guild, name);
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!(
+                            "Open the soundboard in your browser, do not share this link: {}#guild={guild}&token={token}",
+                            self.web_url
+                        ))
+                        .ephemeral(true),
+                ),
+            )
+            .await
+            .expect("Cannot send web login link");
+    }
+
     async fn backup_sounds(&self, ctx: Context, command: CommandInteraction) {
         let Some(guild) = command.guild_id else {
             return;
@@ -1052,7 +1952,7 @@ impl Handler {
                     let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
                     written += metadata.len();
                     archive
-                        .start_file("sounds.json", ZipFileOptions::default())
+                        .start_file(store::BACKUP_METADATA_NAME, ZipFileOptions::default())
                         .expect("Failed to create backup archive");
                     archive
                         .write_all(metadata.as_bytes())
@@ -1182,7 +2082,7 @@ impl Handler {
                 return;
             }
 
-            let members = channel.members(&ctx).expect("Cannot fetch member list");
+            let members = channel.members(ctx).expect("Cannot fetch member list");
             if !(members.len() == 1 && members[0].user.id == self.bot_id.load(Ordering::Relaxed)) {
                 return;
             }
@@ -1210,6 +2110,32 @@ impl Handler {
         let join_voice_channel = CreateCommand::new("join")
             .description("Join your voice channel")
             .kind(CommandType::ChatInput);
+        let play = CreateCommand::new("play")
+            .description("Play a sound of the soundboard")
+            .kind(CommandType::ChatInput)
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "sound", "Sound to play")
+                    .required(false)
+                    .set_autocomplete(true),
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "group", "Group of the sound")
+                    .required(false)
+                    .set_autocomplete(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "random",
+                    "Play a random sound, of the group if any",
+                )
+                .required(false),
+            );
+        let add_message_sound = CreateCommand::new("Add to soundboard").kind(CommandType::Message);
+        let download_user_recording =
+            CreateCommand::new("Download recording").kind(CommandType::User);
+        let download_user_phrases =
+            CreateCommand::new("Download last phrases").kind(CommandType::User);
         let recorder = CreateCommand::new("recorder")
             .description("Manage the recorder whitelist and download recordings")
             .kind(CommandType::ChatInput)
@@ -1290,6 +2216,56 @@ impl Handler {
                     "list",
                     "List all sounds available on this server",
                 )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Only list the sounds of this group",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "search",
+                        "Only list the sounds whose name contains this text",
+                    )
+                    .required(false)
+                    .max_length(MAX_LIST_SEARCH_LENGTH),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "paginated",
+                        "Browse the sounds page by page in a message only visible to you",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "random",
+                        "Add a random sound button",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "latest",
+                        "Add a latest sound button",
+                    )
+                    .required(false),
+                ),
+            )
+            // Dashboard.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "dashboard",
+                    "Post the sounds list, kept up to date when sounds change",
+                )
                 .add_sub_option(
                     CreateCommandOption::new(
                         CommandOptionType::Boolean,
@@ -1365,6 +2341,43 @@ impl Handler {
                         .min_int_value(1),
                     ),
             )
+            // Bulk upload.
+            .add_option(
+                (2..=bulk::MAX_BULK_ATTACHMENTS).fold(
+                    CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        "upload-bulk",
+                        "Upload several sounds at once, named after their files",
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::Attachment,
+                            "sounds",
+                            "Sound file, or ZIP archive of sounds with a folder per group",
+                        )
+                        .required(true),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "group",
+                            "The group to add these sounds to, instead of the folders of the archive",
+                        )
+                        .required(false)
+                        .set_autocomplete(true),
+                    ),
+                    |option, n| {
+                        option.add_sub_option(
+                            CreateCommandOption::new(
+                                CommandOptionType::Attachment,
+                                format!("sound-{n}"),
+                                "Another sound file or ZIP archive",
+                            )
+                            .required(false),
+                        )
+                    },
+                ),
+            )
             // Download.
             .add_option(
                 CreateCommandOption::new(
@@ -1391,6 +2404,32 @@ impl Handler {
                     .set_autocomplete(true),
                 ),
             )
+            // Edit.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "edit",
+                    "Change the name, group, emoji, color and position of a sound at once",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name to edit",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the button",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
             // Rename.
             .add_option(
                 CreateCommandOption::new(
@@ -1460,6 +2499,97 @@ impl Handler {
                     .set_autocomplete(true),
                 ),
             )
+            // Reorder.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "reorder",
+                    "Change the position of a soundboard button in its group",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name to move",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "position",
+                        "New position of the button in its group",
+                    )
+                    .required(true)
+                    .min_int_value(1),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the button",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // Reorder group.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "reorder-group",
+                    "Change the position of a group in the soundboard",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "group", "Group to move")
+                        .required(true)
+                        .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "position",
+                        "New position of the group",
+                    )
+                    .required(true)
+                    .min_int_value(1),
+                ),
+            )
+            // Edit group.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "edit-group",
+                    "Change the default color, header emoji and description of a group",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "group", "Group to edit")
+                        .required(true)
+                        .set_autocomplete(true),
+                ),
+            )
+            // Rename group.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "rename-group",
+                    "Rename a group of the soundboard",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "group", "Group to rename")
+                        .required(true)
+                        .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "new-name",
+                        "New name of the group",
+                    )
+                    .required(true),
+                ),
+            )
             // Change color.
             .add_option(
                 CreateCommandOption::new(
@@ -1564,6 +2694,12 @@ impl Handler {
                 "backup",
                 "Download all sounds and metadata as a zip archive",
             ))
+            // Web.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "web",
+                "Get a login link to play sounds from a browser",
+            ))
             // Logs.
             .add_option(
                 CreateCommandOption::new(
@@ -1606,11 +2742,35 @@ impl Handler {
                     .set_autocomplete(true),
                 ),
             );
+            soundboard = soundboard.add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "delete-group",
+                    "Delete a group and all its sounds from the soundboard",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "group", "Group to delete")
+                        .required(true)
+                        .set_autocomplete(true),
+                ),
+            );
         }
 
-        Command::set_global_commands(ctx, vec![version, join_voice_channel, recorder, soundboard])
-            .await
-            .expect("Global commands creation failure");
+        Command::set_global_commands(
+            ctx,
+            vec![
+                version,
+                join_voice_channel,
+                play,
+                add_message_sound,
+                download_user_recording,
+                download_user_phrases,
+                recorder,
+                soundboard,
+            ],
+        )
+        .await
+        .expect("Global commands creation failure");
         info!("global commands created");
     }
 }
@@ -1638,6 +2798,64 @@ async fn find_voice_channel<H: AsRef<Http>, C: AsRef<Cache>>(
     None
 }
 
+/// Answer a command with a message only its user sees, without pinging
+/// mentioned users.
+async fn respond_ephemeral(ctx: &Context, command: &CommandInteraction, content: String) {
+    command
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            ),
+        )
+        .await
+        .expect("Ephemeral response failure");
+}
+
+/// Answer a modal with a message only its user sees, to reject its input.
+async fn respond_modal_ephemeral(ctx: &Context, modal: &ModalInteraction, content: &str) {
+    modal
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(content),
+            ),
+        )
+        .await
+        .expect("Ephemeral modal response failure");
+}
+
+/// Whether Discord refused a message because of the emoji of a button.
+fn is_invalid_emoji_error(err: &SerenityError) -> bool {
+    match err {
+        SerenityError::Http(HttpError::UnsuccessfulRequest(resp)) => {
+            resp.status_code.as_u16() == StatusCode::BAD_REQUEST
+                && resp.error.code == INVALID_EMOJI_CODE
+                && resp
+                    .error
+                    .errors
+                    .iter()
+                    .any(|sub_error| sub_error.code == INVALID_EMOJI_MESSAGE)
+        }
+        _ => false,
+    }
+}
+
+/// First audio file attached to a message, voice messages included.
+fn audio_attachment(message: &Message) -> Option<&Attachment> {
+    message.attachments.iter().find(|attachment| {
+        attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|kind| kind.starts_with("audio/"))
+    })
+}
+
 fn parse_subcommand(command: &CommandInteraction) -> Option<&str> {
     let first_option = command.data.options.first()?;
     if first_option.kind() != CommandOptionType::SubCommand {
@@ -1646,26 +2864,40 @@ fn parse_subcommand(command: &CommandInteraction) -> Option<&str> {
     Some(&first_option.name)
 }
 
+/// Play a sound of the guild in the voice channel the bot is connected to.
 async fn play_sound(
     manager: Arc<Songbird>,
     soundboard: &Soundboard,
+    now_playing: &NowPlaying,
     guild: GuildId,
     sound: Ulid,
-) -> bool {
-    let Some(wav) = soundboard.get_wav(sound).await else {
-        return false;
-    };
-
-    let Some(call) = manager.get(guild) else {
-        return false;
-    };
+) -> Result<(), PlayError> {
+    let call = manager.get(guild).ok_or(PlayError::NotInVoice)?;
     let mut call_guard = call.lock().await;
     if call_guard.current_channel().is_none() {
-        return false;
+        return Err(PlayError::NotInVoice);
     }
 
-    call_guard.play_input(Input::from(wav));
-    true
+    let pcm = soundboard
+        .get_pcm(guild, sound)
+        .await
+        .ok_or(PlayError::SoundNotFound)?;
+    let track = call_guard.play_input(Input::from(RawAdapter::new(
+        Cursor::new(pcm),
+        recorder::FREQUENCY as u32,
+        1,
+    )));
+    drop(call_guard);
+    now_playing.register(guild, sound, track).await;
+    Ok(())
+}
+
+#[derive(ThisError, Debug)]
+pub enum PlayError {
+    #[error("Cannot find that sound.")]
+    SoundNotFound,
+    #[error("The bot is not in a voice channel.")]
+    NotInVoice,
 }
 
 #[tokio::main]
@@ -1690,19 +2922,53 @@ async fn main() -> ExitCode {
     ));
     Recorder::cleanup_loop(recorder.clone());
 
-    let soundboard = Arc::new(
-        Soundboard::new(
-            options.soundboard_metadata_path,
-            options.sounds_dir_path,
-            options.sound_max_duration,
-            options.sound_cache_duration,
-            options.ffmpeg_path,
-        )
-        .await,
-    );
-    Arc::clone(&soundboard).cache_loop();
+    let events = Arc::new(Events::default());
+    let metrics = Arc::new(Metrics::default());
+    Arc::clone(&metrics).count_events(&events);
+    let soundboard = match Soundboard::new(
+        options.soundboard_metadata_path,
+        options.sound_plays_path,
+        options.groups_path,
+        options.sounds_dir_path,
+        options.sound_max_duration,
+        options.refuse_duplicate_sounds,
+        options.sound_cache_size,
+        options.ffmpeg_path.clone(),
+        Arc::clone(&events),
+    )
+    .await
+    {
+        Ok(soundboard) => Arc::new(soundboard),
+        Err(err) => {
+            error!("failed to read soundboard metadata: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    soundboard.prewarm_cache(options.sound_cache_prewarm).await;
+    Arc::clone(&soundboard).stats_loop();
 
     let history = Arc::new(History::default());
+    let dashboards = Arc::new(Dashboards::load(options.dashboards_path).await);
+    let now_playing = Arc::new(NowPlaying::default());
+
+    let api_tokens = match &options.api_tokens_path {
+        Some(path) => Arc::new(ApiTokens::load(path).await),
+        None => {
+            if !options.soundboard_http_address.is_loopback() {
+                warn!("http endpoint is exposed without API tokens");
+            }
+            Arc::new(ApiTokens::default())
+        }
+    };
+    let web_url = options.web_url.clone().unwrap_or_else(|| {
+        format!(
+            "http://{}/web",
+            SocketAddr::new(
+                options.soundboard_http_address,
+                options.soundboard_http_port
+            )
+        )
+    });
 
     let intents = GatewayIntents::GUILDS
         | GatewayIntents::GUILD_PRESENCES
@@ -1717,10 +2983,16 @@ async fn main() -> ExitCode {
             recorder: Arc::clone(&recorder),
             soundboard: Arc::clone(&soundboard),
             history: Arc::clone(&history),
+            now_playing: Arc::clone(&now_playing),
+            events: Arc::clone(&events),
+            api_tokens: Arc::clone(&api_tokens),
+            web_url,
+            dashboards: Arc::clone(&dashboards),
         })
         .register_songbird_with(Arc::clone(&songbird))
         .await
         .expect("Error creating client");
+    Arc::clone(&dashboards).update_loop(Arc::clone(&client.http), Arc::clone(&soundboard), &events);
 
     let listener = TcpListener::bind(SocketAddr::new(
         options.soundboard_http_address,
@@ -1730,14 +3002,26 @@ async fn main() -> ExitCode {
     .expect("Could not bind socket");
     let server = axum::serve(
         listener,
-        api::router(ApiState {
-            http: Arc::clone(&client.http),
-            cache: Arc::clone(&client.cache),
-            songbird,
-            recorder,
-            soundboard,
-            history,
-        }),
+        api::router(
+            ApiState {
+                http: Arc::clone(&client.http),
+                cache: Arc::clone(&client.cache),
+                songbird,
+                recorder,
+                soundboard,
+                history,
+                now_playing,
+                events,
+                metrics,
+                settings: ApiSettings {
+                    allow_delete: !options.disable_delete,
+                    allow_grey: options.allow_grey,
+                    ffmpeg_path: options.ffmpeg_path,
+                },
+            },
+            api_tokens,
+            options.metrics_public,
+        ),
     );
 
     info!("starting disrecord bot");
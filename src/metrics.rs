@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+use log::warn;
+use serenity::model::id::GuildId;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::{Events, GuildEvent, PlaySource};
+
+/// Counters that no other part of the bot keeps, the rest of the metrics are
+/// gathered when rendered.
+#[derive(Default, Debug)]
+pub struct Metrics {
+    sounds_played: Mutex<HashMap<(GuildId, PlaySource), u64>>,
+    api_requests: Mutex<HashMap<(String, String, u16), u64>>,
+}
+
+impl Metrics {
+    /// Count the sounds played in every guild from their events.
+    pub fn count_events(self: Arc<Self>, events: &Events) {
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok((guild, GuildEvent::SoundPlayed { source, .. })) => {
+                        *self
+                            .sounds_played
+                            .lock()
+                            .expect("Metrics lock poisoned")
+                            .entry((guild, source))
+                            .or_default() += 1;
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("metrics missed {missed} events");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    pub fn count_request(&self, method: &str, route: &str, status: u16) {
+        *self
+            .api_requests
+            .lock()
+            .expect("Metrics lock poisoned")
+            .entry((method.to_owned(), route.to_owned(), status))
+            .or_default() += 1;
+    }
+
+    pub fn write_counters(&self, writer: &mut MetricsWriter) {
+        writer.metric(
+            "disrecord_sounds_played_total",
+            "counter",
+            "Sounds played.",
+            self.sounds_played
+                .lock()
+                .expect("Metrics lock poisoned")
+                .iter()
+                .map(|((guild, source), count)| {
+                    (
+                        labels(&[("guild", &guild.to_string()), ("source", source.as_str())]),
+                        *count as f64,
+                    )
+                }),
+        );
+        writer.metric(
+            "disrecord_api_requests_total",
+            "counter",
+            "HTTP API requests.",
+            self.api_requests
+                .lock()
+                .expect("Metrics lock poisoned")
+                .iter()
+                .map(|((method, route, status), count)| {
+                    (
+                        labels(&[
+                            ("method", method),
+                            ("route", route),
+                            ("status", &status.to_string()),
+                        ]),
+                        *count as f64,
+                    )
+                }),
+        );
+    }
+}
+
+/// Prometheus text exposition format.
+#[derive(Default)]
+pub struct MetricsWriter {
+    out: String,
+}
+
+impl MetricsWriter {
+    pub fn metric(
+        &mut self,
+        name: &str,
+        kind: &str,
+        help: &str,
+        samples: impl IntoIterator<Item = (String, f64)>,
+    ) {
+        let _ = writeln!(self.out, "# HELP {name} {help}");
+        let _ = writeln!(self.out, "# TYPE {name} {kind}");
+        for (labels, value) in samples {
+            let _ = writeln!(self.out, "{name}{labels} {value}");
+        }
+    }
+
+    /// A metric without labels.
+    pub fn value(&mut self, name: &str, kind: &str, help: &str, value: f64) {
+        self.metric(name, kind, help, [(String::new(), value)]);
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+pub fn labels(labels: &[(&str, &str)]) -> String {
+    let labels = labels
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "{name}=\"{}\"",
+                value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n")
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricsWriter, labels};
+
+    #[test]
+    fn text_format() {
+        let mut writer = MetricsWriter::default();
+        writer.value("calls", "gauge", "Voice calls.", 2.0);
+        writer.metric(
+            "plays_total",
+            "counter",
+            "Plays.",
+            [(labels(&[("guild", "1"), ("name", "a \"b\"")]), 3.0)],
+        );
+        assert_eq!(
+            writer.finish(),
+            "# HELP calls Voice calls.\n# TYPE calls gauge\ncalls 2\n\
+             # HELP plays_total Plays.\n# TYPE plays_total counter\n\
+             plays_total{guild=\"1\",name=\"a \\\"b\\\"\"} 3\n"
+        );
+    }
+}
@@ -38,13 +38,25 @@ pub struct Options {
         default_value("15s")
     )]
     pub sound_max_duration: Duration,
+    #[arg(short = 'u', long)]
+    pub refuse_duplicate_sounds: bool,
+    // Not `-c`, which used to take the cache duration: `-c 3m` must not
+    // silently become a 3 MiB cache.
     #[arg(
-        short = 'c',
+        short = 'C',
         long,
-        value_parser(Options::parse_duration),
-        default_value("3m")
+        value_parser(Options::parse_size),
+        default_value("64MiB")
     )]
-    pub sound_cache_duration: Duration,
+    pub sound_cache_size: usize,
+    #[arg(short = 'P', long, default_value("0"))]
+    pub sound_cache_prewarm: usize,
+    #[arg(short = 'n', long, default_value("soundboard-plays"))]
+    pub sound_plays_path: PathBuf,
+    #[arg(short = 'G', long, default_value("soundboard-groups"))]
+    pub groups_path: PathBuf,
+    #[arg(short = 'b', long, default_value("soundboard-dashboards"))]
+    pub dashboards_path: PathBuf,
     #[arg(short = 'f', long, default_value("ffmpeg"))]
     pub ffmpeg_path: PathBuf,
     #[arg(short = 'r', long)]
@@ -55,6 +67,12 @@ pub struct Options {
     pub soundboard_http_address: IpAddr,
     #[arg(short = 'p', long, default_value("8080"))]
     pub soundboard_http_port: u16,
+    #[arg(short = 'k', long)]
+    pub api_tokens_path: Option<PathBuf>,
+    #[arg(short = 'W', long)]
+    pub web_url: Option<String>,
+    #[arg(short = 'M', long)]
+    pub metrics_public: bool,
 }
 
 impl Options {
@@ -62,6 +80,26 @@ impl Options {
         parse_duration::parse(input)
     }
 
+    fn parse_size(input: &str) -> Result<usize, String> {
+        let input = input.trim();
+        let split = input
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(input.len());
+        let (size, unit) = input.split_at(split);
+        let size = size
+            .parse::<usize>()
+            .map_err(|_| format!("invalid size: {input}"))?;
+        let multiplier = match unit.trim().to_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kb" | "kib" => 1 << 10,
+            "m" | "mb" | "mib" => 1 << 20,
+            "g" | "gb" | "gib" => 1 << 30,
+            _ => return Err(format!("invalid size unit: {unit}")),
+        };
+        size.checked_mul(multiplier)
+            .ok_or_else(|| format!("size too large: {input}"))
+    }
+
     pub fn log_level(&self) -> LevelFilter {
         match self.log_level {
             0 => LevelFilter::Error,
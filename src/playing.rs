@@ -0,0 +1,55 @@
+use std::{collections::HashMap, time::Duration};
+
+use serenity::model::id::GuildId;
+use songbird::tracks::TrackHandle;
+use tokio::sync::Mutex;
+use ulid::Ulid;
+
+/// Tracks of sounds played in each guild, forgotten once they end.
+#[derive(Default)]
+pub struct NowPlaying {
+    guild_tracks: Mutex<HashMap<GuildId, Vec<(Ulid, TrackHandle)>>>,
+}
+
+impl NowPlaying {
+    pub async fn register(&self, guild: GuildId, sound: Ulid, track: TrackHandle) {
+        let mut guild_tracks = self.guild_tracks.lock().await;
+        let tracks = guild_tracks.entry(guild).or_default();
+        retain_playing(tracks).await;
+        tracks.push((sound, track));
+    }
+
+    /// Sounds currently played in a guild, with their position.
+    pub async fn get(&self, guild: GuildId) -> Vec<(Ulid, Duration)> {
+        let mut guild_tracks = self.guild_tracks.lock().await;
+        let Some(tracks) = guild_tracks.get_mut(&guild) else {
+            return Vec::new();
+        };
+        retain_playing(tracks).await;
+
+        let mut playing = Vec::with_capacity(tracks.len());
+        for (sound, track) in tracks.iter() {
+            if let Ok(state) = track.get_info().await {
+                playing.push((*sound, state.position));
+            }
+        }
+        if tracks.is_empty() {
+            guild_tracks.remove(&guild);
+        }
+        playing
+    }
+}
+
+async fn retain_playing(tracks: &mut Vec<(Ulid, TrackHandle)>) {
+    let mut playing = Vec::with_capacity(tracks.len());
+    for (sound, track) in tracks.drain(..) {
+        if track
+            .get_info()
+            .await
+            .is_ok_and(|state| !state.playing.is_done())
+        {
+            playing.push((sound, track));
+        }
+    }
+    *tracks = playing;
+}
@@ -16,9 +16,9 @@ use tokio::{
     time::sleep,
 };
 
-pub type Ssrc = u32;
+pub use crate::wav::FREQUENCY;
 
-pub const FREQUENCY: usize = 48_000;
+pub type Ssrc = u32;
 
 // Log every voice data packet on debug and only one every five minutes on info
 // level.
@@ -26,11 +26,10 @@ macro_rules! log_voice_data {
     ($st:tt, $($arg:tt)+) => {
         if log_enabled!(Level::Debug) {
             debug!($($arg)+);
-        } else if log_enabled!(Level::Info) {
-            $st.voice_data_received += 1;
-            if $st.voice_data_received % 15_000 == 0 { // ~ Once every five minute of voice data.
-                info!($($arg)+);
-            }
+        } else if log_enabled!(Level::Info)
+            && $st.voice_data_received % 15_000 == 0 // ~ Once every five minute of voice data.
+        {
+            info!($($arg)+);
         }
     };
 }
@@ -81,6 +80,10 @@ impl Recorder {
         self.whitelist.clone()
     }
 
+    pub fn is_whitelisted(&self, user: UserId) -> bool {
+        self.whitelist.contains(&user)
+    }
+
     pub async fn add_whitelist(&mut self, user: UserId) {
         info!("adding user {user} to whitelist");
         if self.whitelist.insert(user) {
@@ -152,6 +155,15 @@ impl Recorder {
         }
     }
 
+    /// Guilds whose voice is being recorded, with their recorder.
+    pub fn guild_recorders(
+        &self,
+    ) -> impl Iterator<Item = (GuildId, &UnboundedSender<RecorderAction>)> {
+        self.guilds
+            .iter()
+            .map(|(guild, recorder)| (*guild, recorder))
+    }
+
     pub fn cleanup_loop(recorder: Arc<Mutex<Self>>) {
         tokio::spawn(async move {
             loop {
@@ -170,10 +182,16 @@ pub struct GuildRecorder {
     whitelist: HashSet<UserId>,
     buffer_size: Duration,
     voice_data: HashMap<Ssrc, UserVoiceData>,
-    voice_data_received: usize,
+    voice_data_received: u64,
     clean_timeout: Duration,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct GuildRecorderStats {
+    pub buffered_bytes: usize,
+    pub voice_packets: u64,
+}
+
 impl GuildRecorder {
     fn run_loop(mut self) -> UnboundedSender<RecorderAction> {
         let (tx, mut rx) = mpsc::unbounded_channel::<RecorderAction>();
@@ -213,6 +231,7 @@ impl GuildRecorder {
                         info!("mapped ssrc {ssrc} to user {id}");
                     }
                     RecorderAction::RegisterVoiceData(ssrc, data) => {
+                        self.voice_data_received += 1;
                         log_voice_data!(
                             self,
                             "registering {} bytes voice data for ssrc {ssrc}",
@@ -253,7 +272,8 @@ impl GuildRecorder {
                             "fetched {} bytes of data for user {user}",
                             data.as_ref().map(|d| d.len()).unwrap_or(0) * 2
                         );
-                        tx.send(data).expect("Voice data send failed.");
+                        // The API request may have been dropped meanwhile.
+                        let _ = tx.send(data);
                     }
                     RecorderAction::GetVoiceDataChunks(user, len, min_duration, tx) => {
                         info!("fetching data for user {user}");
@@ -298,7 +318,20 @@ impl GuildRecorder {
                             "fetched {} voice chunks for user {user}",
                             data.as_ref().map(|d| d.len()).unwrap_or(0)
                         );
-                        tx.send(data).expect("Voice data chunks send failed.");
+                        let _ = tx.send(data);
+                    }
+                    RecorderAction::GetStats(tx) => {
+                        let buffered_bytes = self
+                            .voice_data
+                            .values()
+                            .filter_map(|user_data| user_data.data.as_ref())
+                            .map(|data| data.len() * 2)
+                            .sum();
+                        // The metrics scrape may have given up waiting.
+                        let _ = tx.send(GuildRecorderStats {
+                            buffered_bytes,
+                            voice_packets: self.voice_data_received,
+                        });
                     }
                     RecorderAction::CleanOld => {
                         debug!("cleaning users voice data that hasn't speak for a while");
@@ -371,5 +404,6 @@ pub enum RecorderAction {
         Duration,
         OneshotSender<Option<Vec<Vec<i16>>>>,
     ),
+    GetStats(OneshotSender<GuildRecorderStats>),
     CleanOld,
 }
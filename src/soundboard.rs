@@ -1,349 +1,822 @@
 use std::{
-    collections::{HashMap, hash_map::DefaultHasher},
+    cmp::Reverse,
+    collections::{HashMap, HashSet, hash_map::DefaultHasher},
     ffi::OsStr,
     hash::{Hash, Hasher},
     path::{Path, PathBuf},
     process::Stdio,
-    sync::Arc,
+    sync::{
+        Arc, Mutex as SyncMutex, MutexGuard as SyncMutexGuard,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
     time::{Duration, Instant},
 };
 
-use bincode::Options;
 use itertools::Itertools;
-use log::info;
+use log::{debug, info, warn};
 use rand::seq::IteratorRandom;
 use regex::Regex;
-use serde::{Deserialize, Serialize};
-use serde_json::{Value, json};
 use serenity::{
     all::ButtonStyle,
     model::{channel::Attachment, id::GuildId},
 };
 use thiserror::Error as ThisError;
-use tokio::{fs, fs::OpenOptions, io::AsyncWriteExt, process::Command, sync::Mutex, time::sleep};
+use tokio::{
+    fs,
+    fs::OpenOptions,
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::Command,
+    sync::{Mutex, RwLock, RwLockWriteGuard},
+    time::sleep,
+};
 use ulid::Ulid;
 
-use crate::{button, wav};
+use crate::{
+    bulk::{self, BulkFile, BulkSound, BulkUpload, MAX_BULK_SOUNDS},
+    button,
+    cache::{CacheStats, SoundCache},
+    events::{Events, GuildEvent},
+    fuzzy,
+    store::{
+        self, BackupGroup, BackupSound, GroupMetadata, INDEX_GAP, METADATA_VERSION, Metadata,
+        SoundMetadata, StoreError, file_path,
+    },
+    wav,
+};
+
+/// Discord limit of autocomplete choice names.
+const MAX_CHOICE_LABEL: usize = 100;
 
+/// Sounds metadata are kept behind a `RwLock` that is never held while reading
+/// or writing sound files, so a slow disk never blocks other guilds. The
+/// metadata file itself is protected by its own lock, taken before releasing
+/// the state lock, to keep writes in the same order as the mutations.
 #[derive(Debug)]
 pub struct Soundboard {
     metadata_path: PathBuf,
+    plays_path: PathBuf,
+    groups_path: PathBuf,
     sounds_dir_path: PathBuf,
     max_duration: Duration,
-    cache_duration: Duration,
+    refuse_duplicates: bool,
     ffmpeg_path: PathBuf,
-    sounds: Mutex<HashMap<Ulid, Sound>>,
+    state: RwLock<State>,
+    metadata_file: Mutex<()>,
+    groups_file: Mutex<()>,
+    cache: SyncMutex<SoundCache>,
+    plays_changed: AtomicBool,
+    transcoding: TranscodingCounters,
+    events: Arc<Events>,
+}
+
+#[derive(Default, Debug)]
+struct TranscodingCounters {
+    successes: AtomicU64,
+    failures: AtomicU64,
+    micros: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TranscodingStats {
+    pub successes: u64,
+    pub failures: u64,
+    /// Total time spent transcoding.
+    pub duration: Duration,
 }
 
 impl Soundboard {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         metadata_path: PathBuf,
+        plays_path: PathBuf,
+        groups_path: PathBuf,
         sounds_dir_path: PathBuf,
         max_duration: Duration,
-        cache_duration: Duration,
+        refuse_duplicates: bool,
+        cache_size: usize,
         ffmpeg_path: PathBuf,
-    ) -> Self {
-        let sounds = fs::read(&metadata_path)
+        events: Arc<Events>,
+    ) -> Result<Self, StoreError> {
+        let plays = fs::read(&plays_path)
             .await
             .ok()
-            .map(|file| {
-                let mut deserializer = bincode::Deserializer::from_slice(
-                    &file,
-                    bincode::DefaultOptions::new()
-                        .with_fixint_encoding()
-                        .allow_trailing_bytes(),
-                );
-                let mut sounds = HashMap::new();
-                loop {
-                    let metadata = match SoundMetadata::deserialize(&mut deserializer) {
-                        Ok(metadata) => metadata,
-                        Err(_) => break,
-                    };
-                    sounds.insert(
-                        metadata.id,
-                        Sound {
-                            metadata,
-                            data: CachedSound::Fs,
-                        },
-                    );
-                }
-                sounds
-            })
+            .and_then(|file| bincode::deserialize::<HashMap<Ulid, u64>>(&file).ok())
             .unwrap_or_default();
-        info!("loaded {} sounds", sounds.len());
+        let mut state = State::default();
+        if let Ok(file) = fs::read(&groups_path).await {
+            match serde_json::from_slice(&file) {
+                Ok(groups) => state.groups = groups,
+                Err(err) => warn!("invalid soundboard groups file, using default groups: {err}"),
+            }
+        }
+        let mut migrate = false;
+        if let Ok(file) = fs::read(&metadata_path).await {
+            let sounds = match store::decode_metadata(&file)? {
+                Metadata::V2(sounds) => sounds,
+                Metadata::V1(legacy_sounds) => {
+                    migrate = true;
+                    let mut sounds = Vec::new();
+                    for legacy in legacy_sounds {
+                        let hash = match fs::read(file_path(&sounds_dir_path, legacy.id)).await {
+                            Ok(data) => Some(wav::pcm_hash(&data)),
+                            Err(err) => {
+                                warn!("failed to hash sound {}: {err}", legacy.id);
+                                None
+                            }
+                        };
+                        sounds.push(legacy.upgrade(hash));
+                    }
+                    sounds
+                }
+            };
+            for metadata in sounds {
+                let plays = plays.get(&metadata.id).copied().unwrap_or(0);
+                let duration = fs::metadata(file_path(&sounds_dir_path, metadata.file))
+                    .await
+                    .map(|file| wav::duration_from_size(file.len() as usize))
+                    .unwrap_or_default();
+                state.insert(Sound::new(metadata, plays, duration));
+            }
+        }
+        info!(
+            "loaded {} sounds for {} guilds",
+            state.sounds.len(),
+            state.guilds.len()
+        );
 
-        Self {
+        let soundboard = Self {
             metadata_path,
+            plays_path,
+            groups_path,
             sounds_dir_path,
             max_duration,
-            cache_duration,
+            refuse_duplicates,
             ffmpeg_path,
-            sounds: Mutex::new(sounds),
+            state: RwLock::new(state),
+            metadata_file: Mutex::new(()),
+            groups_file: Mutex::new(()),
+            cache: SyncMutex::new(SoundCache::new(cache_size)),
+            plays_changed: AtomicBool::new(false),
+            transcoding: TranscodingCounters::default(),
+            events,
+        };
+
+        // Keep a copy of the legacy file, in case the migration goes wrong.
+        if migrate {
+            info!("migrating soundboard metadata to format version {METADATA_VERSION}");
+            fs::copy(
+                &soundboard.metadata_path,
+                soundboard.metadata_path.with_extension("v1"),
+            )
+            .await
+            .expect("Failed to backup legacy soundboard metadata");
+            soundboard
+                .overwrite_metadata_file(soundboard.state.write().await)
+                .await
+                .expect("Failed to migrate soundboard metadata");
+        }
+
+        if soundboard.normalize_files().await
+            && let Err(err) = soundboard
+                .overwrite_metadata_file(soundboard.state.write().await)
+                .await
+        {
+            warn!("failed to save hashes of normalized sounds: {err}");
+        }
+
+        Ok(soundboard)
+    }
+
+    /// Transcode the files stored by older versions in the format of their
+    /// upload, which can't be played without decoding. Only headers are read
+    /// to find them. Returns whether the metadata of some sounds changed.
+    async fn normalize_files(&self) -> bool {
+        let mut state = self.state.write().await;
+        let mut changed = false;
+        for file in state.files.keys().copied().collect_vec() {
+            let path = file_path(&self.sounds_dir_path, file);
+            let mut header = [0; wav::HEADER_SIZE];
+            let valid = match fs::File::open(&path).await {
+                Ok(mut opened) => {
+                    let len = opened.metadata().await.map_or(0, |m| m.len() as usize);
+                    opened.read_exact(&mut header).await.is_ok()
+                        && wav::has_valid_header(&header, len)
+                }
+                Err(_) => continue,
+            };
+            if valid {
+                continue;
+            }
+
+            let normalized = match fs::read(&path).await {
+                Ok(data) => self.transcode(data, "sound.wav").await,
+                Err(_) => Err(SoundboardError::SoundFetch),
+            };
+            let data = match normalized {
+                Ok(data) => data,
+                Err(err) => {
+                    warn!("failed to normalize sound file {file}: {err}");
+                    continue;
+                }
+            };
+            if let Err(err) = fs::write(&path, &data).await {
+                warn!("failed to write normalized sound file {file}: {err}");
+                continue;
+            }
+
+            let hash = wav::pcm_hash(&data);
+            let duration = wav::duration_from_size(data.len());
+            for sound in state
+                .sounds
+                .values_mut()
+                .filter(|sound| sound.metadata.file == file)
+            {
+                sound.metadata.hash = Some(hash);
+                sound.duration = duration;
+            }
+            info!("normalized sound file {file}");
+            changed = true;
+        }
+        changed
+    }
+
+    /// Load the `count` most played sounds in the cache, within the limits of
+    /// its size.
+    pub async fn prewarm_cache(&self, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        // Insert the most played sounds last, so they are the last to be evicted.
+        let most_played = self
+            .state
+            .read()
+            .await
+            .sounds
+            .values()
+            .map(|sound| (sound.plays.load(Ordering::Relaxed), sound.metadata.file))
+            .filter(|(plays, _file)| *plays > 0)
+            .sorted()
+            .rev()
+            .take(count)
+            .rev()
+            .map(|(_plays, file)| file)
+            .collect_vec();
+        for file in most_played {
+            match fs::read(file_path(&self.sounds_dir_path, file)).await {
+                Ok(data) => self.cache().insert(file, Arc::from(wav::decode_f32(&data))),
+                Err(err) => warn!("failed to prewarm sound file {file}: {err}"),
+            }
         }
+
+        let stats = self.cache_stats();
+        info!(
+            "prewarmed cache with {} sounds ({} bytes)",
+            stats.entries, stats.size
+        );
     }
 
-    pub fn cache_loop(self: Arc<Self>) {
+    pub fn stats_loop(self: Arc<Self>) {
         tokio::spawn(async move {
             loop {
                 sleep(Duration::from_secs(30)).await;
-                for sound in self.sounds.lock().await.values_mut() {
-                    if let CachedSound::Cached(_, last) = &mut sound.data {
-                        if last.elapsed() > self.cache_duration {
-                            sound.data = CachedSound::Fs;
-                        }
-                    }
+                if self.plays_changed.swap(false, Ordering::Relaxed)
+                    && let Err(err) = self.overwrite_plays_file().await
+                {
+                    warn!("failed to save sounds play counts: {err}");
                 }
+
+                let stats = self.cache_stats();
+                debug!(
+                    "sound cache: {} sounds, {}/{} bytes, {} hits, {} misses",
+                    stats.entries, stats.size, stats.max_size, stats.hits, stats.misses
+                );
             }
         });
     }
 
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache().stats()
+    }
+
+    pub fn transcoding_stats(&self) -> TranscodingStats {
+        let counters = &self.transcoding;
+        TranscodingStats {
+            successes: counters.successes.load(Ordering::Relaxed),
+            failures: counters.failures.load(Ordering::Relaxed),
+            duration: Duration::from_micros(counters.micros.load(Ordering::Relaxed)),
+        }
+    }
+
     pub async fn list(&self, guild: GuildId) -> Vec<(String, Vec<SoundMetadata>)> {
-        self.sounds
-            .lock()
+        self.search(guild, None, None)
             .await
-            .values()
-            .filter(|sound| sound.metadata.guild == guild.get())
+            .into_iter()
+            .map(|(group, sounds)| {
+                (
+                    group,
+                    sounds.into_iter().map(|sound| sound.metadata).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Sounds whose name and group contain the searched ones, by group and in
+    /// the order of the soundboard.
+    pub async fn search(
+        &self,
+        guild: GuildId,
+        name: Option<&str>,
+        group: Option<&str>,
+    ) -> Vec<(String, Vec<SoundDetails>)> {
+        let name_regex = name.map(search_regex);
+        let group_regex = group.map(search_regex);
+        let state = self.state.read().await;
+        state
+            .guild_sounds(guild)
+            .filter(|sound| {
+                name_regex
+                    .as_ref()
+                    .is_none_or(|rg| rg.is_match(&sound.metadata.name))
+                    && group_regex
+                        .as_ref()
+                        .is_none_or(|rg| rg.is_match(&sound.metadata.group))
+            })
             .into_group_map_by(|sound| &sound.metadata.group)
             .into_iter()
-            .sorted_by(|(g1, _), (g2, _)| g1.cmp(g2))
+            .sorted_by(|(g1, _), (g2, _)| {
+                state
+                    .group_rank(guild, g1)
+                    .cmp(&state.group_rank(guild, g2))
+            })
             .map(|(g, s)| {
-                let mut sounds = s.into_iter().map(|s| s.metadata.clone()).collect_vec();
-                sounds.sort_by_key(|sound| sound.index);
+                let mut sounds = s.into_iter().map(Sound::details).collect_vec();
+                sounds.sort_by_key(|sound| sound.metadata.index);
                 (g.clone(), sounds)
             })
             .collect()
     }
 
-    pub async fn names_matching(&self, guild: GuildId, search: &str, max: usize) -> Vec<String> {
-        let regex = search_regex(search);
-        self.sounds
-            .lock()
+    pub async fn details(&self, guild: GuildId, id: Ulid) -> Option<SoundDetails> {
+        let state = self.state.read().await;
+        if !state.contains(guild, id) {
+            return None;
+        }
+        state.sounds.get(&id).map(Sound::details)
+    }
+
+    /// Sounds best matching the search, the most played first among equally
+    /// good matches, labeled with their group.
+    pub async fn sounds_matching(
+        &self,
+        guild: GuildId,
+        search: &str,
+        plays: &HashMap<Ulid, u32>,
+        max: usize,
+    ) -> Vec<(String, Ulid)> {
+        self.state
+            .read()
             .await
-            .values()
+            .guild_sounds(guild)
             .filter_map(|sound| {
-                (sound.metadata.guild == guild.get() && regex.is_match(&sound.metadata.name))
-                    .then(|| sound.metadata.name.clone())
+                let rank = fuzzy::rank(search, &sound.metadata.name)?;
+                let plays = plays.get(&sound.metadata.id).copied().unwrap_or(0);
+                Some((Reverse(rank), Reverse(plays), &sound.metadata))
+            })
+            .sorted_by(|(r1, p1, s1), (r2, p2, s2)| {
+                (r1, p1, &s1.name, &s1.group).cmp(&(r2, p2, &s2.name, &s2.group))
             })
-            .sorted()
-            .dedup()
             .take(max)
+            .map(|(_, _, metadata)| {
+                let label = format!("{} ({})", metadata.name, metadata.group);
+                (label.chars().take(MAX_CHOICE_LABEL).collect(), metadata.id)
+            })
             .collect()
     }
 
-    pub async fn groups_matching(&self, guild: GuildId, group: &str, max: usize) -> Vec<String> {
-        let regex = search_regex(group);
-        self.sounds
-            .lock()
-            .await
-            .values()
-            .filter_map(|sound| {
-                (sound.metadata.guild == guild.get() && regex.is_match(&sound.metadata.group))
-                    .then(|| sound.metadata.group.clone())
+    /// Groups best matching the search, the most played first among equally
+    /// good matches.
+    pub async fn groups_matching(
+        &self,
+        guild: GuildId,
+        search: &str,
+        plays: &HashMap<Ulid, u32>,
+        max: usize,
+    ) -> Vec<String> {
+        let state = self.state.read().await;
+        let mut groups = HashMap::<&str, u32>::new();
+        for sound in state.guild_sounds(guild) {
+            *groups.entry(&sound.metadata.group).or_default() +=
+                plays.get(&sound.metadata.id).copied().unwrap_or(0);
+        }
+        groups
+            .into_iter()
+            .filter_map(|(group, plays)| {
+                Some((Reverse(fuzzy::rank(search, group)?), Reverse(plays), group))
             })
             .sorted()
-            .dedup()
             .take(max)
+            .map(|(_, _, group)| group.to_owned())
             .collect()
     }
 
-    pub async fn get_wav(&self, id: Ulid) -> Option<Vec<u8>> {
-        self.sounds
-            .lock()
-            .await
-            .get_mut(&id)?
-            .get_wav_data(&self.sounds_dir_path, true)
+    /// Get the decoded f32 PCM samples of a sound to play it, and count it as
+    /// played.
+    pub async fn get_pcm(&self, guild: GuildId, id: Ulid) -> Option<Arc<[u8]>> {
+        let file = {
+            let state = self.state.read().await;
+            if !state.contains(guild, id) {
+                return None;
+            }
+            state.sounds.get(&id)?.metadata.file
+        };
+
+        let cached = self.cache().get(&file);
+        let pcm = match cached {
+            Some(pcm) => pcm,
+            None => {
+                let pcm = Arc::<[u8]>::from(wav::decode_f32(&self.read_wav(file).await?));
+                self.cache().insert(file, Arc::clone(&pcm));
+                pcm
+            }
+        };
+
+        // Only count sounds actually played, which may have been deleted since.
+        if let Some(sound) = self.state.read().await.sounds.get(&id) {
+            sound.plays.fetch_add(1, Ordering::Relaxed);
+            self.plays_changed.store(true, Ordering::Relaxed);
+        }
+        Some(pcm)
+    }
+
+    pub async fn get_wav(&self, guild: GuildId, id: Ulid) -> Result<Vec<u8>, SoundboardError> {
+        let file = {
+            let state = self.state.read().await;
+            if !state.contains(guild, id) {
+                return Err(SoundboardError::SoundNotFound);
+            }
+            state
+                .sounds
+                .get(&id)
+                .ok_or(SoundboardError::SoundNotFound)?
+                .metadata
+                .file
+        };
+        self.read_wav(file)
             .await
+            .ok_or(SoundboardError::SoundNotFound)
     }
 
+    /// Read the WAV of a sound found by name or id, along with its name.
     pub async fn get_wav_by_name(
         &self,
         guild: GuildId,
         name: &str,
         group: Option<&str>,
-    ) -> Result<Vec<u8>, SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
-
-        let mut sounds = self.sounds.lock().await;
-        let mut matching = sounds.values_mut().filter(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true)
-        });
-        let sound = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
-
-        sound
-            .get_wav_data(&self.sounds_dir_path, true)
+    ) -> Result<(String, Vec<u8>), SoundboardError> {
+        let (name, file) = {
+            let state = self.state.read().await;
+            let metadata = &state.find(guild, name, group)?.metadata;
+            (metadata.name.clone(), metadata.file)
+        };
+        let data = self
+            .read_wav(file)
             .await
-            .ok_or(SoundboardError::SoundNotFound)
+            .ok_or(SoundboardError::SoundNotFound)?;
+        Ok((name, data))
     }
 
+    /// Download a Discord attachment and add it as a sound, see `add`.
     #[allow(clippy::too_many_arguments)]
-    pub async fn add(
+    pub async fn add_attachment(
         &self,
         attachment: &Attachment,
         guild: GuildId,
         name: String,
         emoji: Option<String>,
         color: ButtonStyle,
-        mut group: String,
+        group: String,
         requested_index: Option<usize>,
-    ) -> Result<Ulid, SoundboardError> {
-        // Verify duration.
+    ) -> Result<(Ulid, Option<String>), SoundboardError> {
+        // Verify duration before downloading.
         if wav::duration_from_size(attachment.size as usize) > self.max_duration {
             return Err(SoundboardError::TooLong);
         }
 
-        // Fetch sound data.
         let data = attachment
             .download()
             .await
             .map_err(|_| SoundboardError::SoundFetch)?;
+        self.add(
+            data,
+            &attachment.filename,
+            guild,
+            name,
+            emoji,
+            color,
+            group,
+            requested_index,
+        )
+        .await
+    }
 
-        // If sound is already PCM s16le WAV, keep it as is, transcode it otherwise.
-        let data = if wav::is_valid_pcm_s16le(&data) {
-            data
-        } else {
-            let filename = PathBuf::from(&attachment.filename);
-            let extension = filename
-                .extension()
-                .and_then(OsStr::to_str)
-                .ok_or(SoundboardError::InvalidSound)?;
-
-            let mut cmd = Command::new(&self.ffmpeg_path);
-            cmd.args(["-f", extension]) // Input file format.
-                .args(["-i", "-"]) // Read from stdin.
-                .args(["-f", "wav"]) // Output to raw PCM.
-                .arg("-") // Output to stdout.
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::null());
-            let mut child = cmd
-                .spawn()
-                .map_err(|_| SoundboardError::TranscodingFailed)?;
-
-            let mut stdin = child
-                .stdin
-                .take()
-                .ok_or(SoundboardError::TranscodingFailed)?;
-            tokio::spawn(async move {
-                stdin
-                    .write_all(&data)
+    /// Add the sounds of several attachments one after the other, ZIP
+    /// archives being extracted. Sounds are named after their files and added
+    /// to `group`, or else to the folder of their file in its archive. Returns
+    /// the outcome of every file, and the number of files left out past the
+    /// limit of a bulk upload.
+    pub async fn add_bulk(
+        &self,
+        attachments: &[&Attachment],
+        guild: GuildId,
+        group: Option<&str>,
+        with_grey: bool,
+    ) -> (Vec<BulkUpload>, usize) {
+        let mut files = Vec::new();
+        let mut left_out = 0;
+        for attachment in attachments {
+            let limit = MAX_BULK_SOUNDS.saturating_sub(files.len());
+            let (name, _) = bulk::sound_name(Path::new(&attachment.filename))
+                .unwrap_or_else(|| (attachment.filename.clone(), None));
+            let failed = |err| BulkFile {
+                path: attachment.filename.clone(),
+                name: name.clone(),
+                folder: None,
+                data: Err(err),
+            };
+
+            if bulk::is_archive(&attachment.filename) {
+                let archive = match attachment.download().await {
+                    Ok(data) => {
+                        let max_duration = self.max_duration;
+                        tokio::task::spawn_blocking(move || {
+                            bulk::read_archive(data, max_duration, limit)
+                        })
+                        .await
+                        .expect("Archive extraction panicked")
+                    }
+                    Err(_) => Err(SoundboardError::SoundFetch),
+                };
+                match archive {
+                    Ok((archived, archive_left_out)) => {
+                        files.extend(archived);
+                        left_out += archive_left_out;
+                    }
+                    Err(err) => files.push(failed(err)),
+                }
+            } else if limit == 0 {
+                left_out += 1;
+            } else if wav::duration_from_size(attachment.size as usize) > self.max_duration {
+                files.push(failed(SoundboardError::TooLong));
+            } else {
+                files.push(BulkFile {
+                    path: attachment.filename.clone(),
+                    name: name.clone(),
+                    folder: None,
+                    data: attachment
+                        .download()
+                        .await
+                        .map_err(|_| SoundboardError::SoundFetch),
+                });
+            }
+        }
+
+        let mut uploads = Vec::with_capacity(files.len());
+        for file in files {
+            let Some(group) = group.map(str::to_owned).or(file.folder) else {
+                uploads.push(BulkUpload {
+                    path: file.path,
+                    result: Err(SoundboardError::MissingGroup),
+                });
+                continue;
+            };
+            let result = match file.data {
+                Ok(data) => {
+                    let color = self
+                        .default_color(guild, &group, &file.name, with_grey)
+                        .await;
+                    self.add(
+                        data,
+                        &file.path,
+                        guild,
+                        file.name.clone(),
+                        None,
+                        color,
+                        group.clone(),
+                        None,
+                    )
                     .await
-                    .expect("Failed to write sound to ffmpeg");
+                    .map(|(_, duplicate)| BulkSound {
+                        name: file.name,
+                        group,
+                        duplicate,
+                    })
+                }
+                Err(err) => Err(err),
+            };
+            uploads.push(BulkUpload {
+                path: file.path,
+                result,
             });
+        }
+        (uploads, left_out)
+    }
 
-            let out = child
-                .wait_with_output()
-                .await
-                .map_err(|_| SoundboardError::TranscodingFailed)?;
-            if !out.status.success() || out.stdout.len() % 2 != 0 {
-                return Err(SoundboardError::TranscodingFailed);
-            }
-
-            out.stdout
+    /// Add a sound and return its id, along with the name of an identical
+    /// sound of the guild if there is one and duplicates are allowed. The
+    /// extension of the filename is used as format for transcoding.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add(
+        &self,
+        data: Vec<u8>,
+        filename: &str,
+        guild: GuildId,
+        name: String,
+        emoji: Option<String>,
+        color: ButtonStyle,
+        group: String,
+        requested_index: Option<usize>,
+    ) -> Result<(Ulid, Option<String>), SoundboardError> {
+        // If sound is already mono PCM s16le WAV at 48 kHz, only drop its extra
+        // chunks, transcode it otherwise.
+        let data = match wav::normalize(&data) {
+            Some(data) => data,
+            None => self.transcode(data, filename).await?,
         };
 
-        let mut sounds = self.sounds.lock().await;
+        // Verify duration, which compressed formats hide in their size.
+        if wav::duration_from_size(data.len()) > self.max_duration {
+            return Err(SoundboardError::TooLong);
+        }
 
-        // Find similar existing group.
-        let group_regex = match_regex(&group);
-        group = sounds
-            .values()
-            .find_map(|sound| {
-                group_regex
-                    .is_match(&sound.metadata.group)
-                    .then(|| sound.metadata.group.clone())
-            })
-            .unwrap_or(group);
+        // Share the file of an identical sound from any guild, or write a new
+        // one before locking and remove it if the metadata turn out to be
+        // invalid.
+        let hash = wav::pcm_hash(&data);
+        let id = Ulid::new();
+        let shared_file = {
+            let state = self.state.read().await;
+            if self.refuse_duplicates
+                && let Some(duplicate) = state.guild_duplicate(guild, hash)
+            {
+                return Err(SoundboardError::DuplicateSound(
+                    duplicate.metadata.name.clone(),
+                ));
+            }
+            state.file_with_hash(hash)
+        };
+        let mut file = match shared_file {
+            Some(file) => file,
+            None => {
+                fs::write(file_path(&self.sounds_dir_path, id), &data)
+                    .await
+                    .map_err(|_| SoundboardError::SoundWrite)?;
+                id
+            }
+        };
 
-        // Check if name is already taken in this group.
-        let name_regex = match_regex(&name);
-        if sounds.values().any(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && sound.metadata.group == group
-        }) {
-            return Err(SoundboardError::NameTaken);
+        let mut state = self.state.write().await;
+
+        // The shared file may have been deleted since, in which case its own
+        // file is written without holding the lock, no other sound using it.
+        if file != id && !state.files.contains_key(&file) {
+            drop(state);
+            fs::write(file_path(&self.sounds_dir_path, id), &data)
+                .await
+                .map_err(|_| SoundboardError::SoundWrite)?;
+            file = id;
+            state = self.state.write().await;
         }
 
-        // Resolve index position.
-        let mut overwrite_required = false;
-        let group_sounds = sounds
-            .values()
-            .filter(|s| s.metadata.guild == guild.get() && s.metadata.group == group);
-        let last_index = group_sounds.clone().map(|s| s.metadata.index).max();
-
-        // IDEA: Append with gap of 1M. And when inserting between, insert at equal
-        // distance between (N-1) and (N+1).
-        let index = match (last_index, requested_index) {
-            (None, _) => 0,
-            (Some(last_index), None) => last_index + 1,
-            (Some(last_index), Some(requested_index)) => {
-                let group_len = group_sounds.clone().count();
-                if requested_index >= group_len {
-                    last_index + 1
-                } else {
-                    overwrite_required = true;
-                    let mut sounds = sounds
-                        .values_mut()
-                        .filter(|s| s.metadata.guild == guild.get() && s.metadata.group == group)
-                        .collect_vec();
-                    sounds.sort_by_key(|s| s.metadata.index);
-                    let prev_index = sounds[requested_index].metadata.index;
-                    for sound in &mut sounds[requested_index..] {
-                        sound.metadata.index += 1;
-                    }
-                    prev_index
-                }
+        let duplicate = state
+            .guild_duplicate(guild, hash)
+            .map(|sound| sound.metadata.name.clone());
+        let prepared = match &duplicate {
+            Some(duplicate) if self.refuse_duplicates => {
+                Err(SoundboardError::DuplicateSound(duplicate.clone()))
             }
+            _ => state.prepare_insert(guild, id, name, emoji, color, group, requested_index),
         };
-
-        let id = Ulid::new();
-        let metadata = SoundMetadata {
-            guild: guild.get(),
-            id,
-            name,
-            emoji,
-            color,
-            group,
-            index,
+        let (mut metadata, overwrite_required) = match prepared {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                drop(state);
+                if file == id {
+                    let _ = fs::remove_file(file_path(&self.sounds_dir_path, id)).await;
+                }
+                return Err(err);
+            }
         };
-
-        // Write sound to disk.
-        fs::write(metadata.get_file_path(&self.sounds_dir_path), &data)
-            .await
-            .map_err(|_| SoundboardError::SoundWrite)?;
+        metadata.file = file;
+        metadata.hash = Some(hash);
+        state.insert(Sound::new(
+            metadata.clone(),
+            0,
+            wav::duration_from_size(data.len()),
+        ));
+        self.cache().insert(file, Arc::from(wav::decode_f32(&data)));
 
         // Write metadata to disk (partial or full overwrite).
-        sounds.insert(
-            metadata.id,
-            Sound {
-                metadata: metadata.clone(),
-                data: CachedSound::Cached(data, Instant::now()),
-            },
-        );
         if overwrite_required {
-            self.overwrite_metadata_file(&sounds).await?;
+            self.overwrite_metadata_file(state).await?;
         } else {
+            let record =
+                store::encode_record(&metadata).map_err(|_| SoundboardError::SoundWrite)?;
+            let _file_guard = self.metadata_file.lock().await;
+            drop(state);
             let mut file = OpenOptions::new()
                 .append(true)
                 .create(true)
                 .open(&self.metadata_path)
                 .await
                 .map_err(|_| SoundboardError::SoundWrite)?;
-            file.write(&bincode::serialize(&metadata).map_err(|_| SoundboardError::SoundWrite)?)
+            let empty = file
+                .metadata()
+                .await
+                .map_err(|_| SoundboardError::SoundWrite)?
+                .len()
+                == 0;
+            if empty {
+                file.write_all(&store::metadata_header())
+                    .await
+                    .map_err(|_| SoundboardError::SoundWrite)?;
+            }
+            file.write_all(&record)
                 .await
                 .map_err(|_| SoundboardError::SoundWrite)?;
         }
 
-        Ok(id)
+        self.events.publish(
+            guild,
+            GuildEvent::SoundAdded {
+                id,
+                name: metadata.name,
+                group: metadata.group,
+            },
+        );
+        Ok((id, duplicate))
+    }
+
+    /// Transcode a sound to mono PCM s16le WAV at 48 kHz, the format played
+    /// without decoding, and count the transcoding.
+    async fn transcode(&self, data: Vec<u8>, filename: &str) -> Result<Vec<u8>, SoundboardError> {
+        let started = Instant::now();
+        let transcoded = self.run_ffmpeg(data, filename).await;
+        let stats = &self.transcoding;
+        if transcoded.is_ok() {
+            stats.successes.fetch_add(1, Ordering::Relaxed);
+        } else {
+            stats.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        stats
+            .micros
+            .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+        transcoded
+    }
+
+    async fn run_ffmpeg(&self, data: Vec<u8>, filename: &str) -> Result<Vec<u8>, SoundboardError> {
+        let filename = PathBuf::from(filename);
+        let extension = filename
+            .extension()
+            .and_then(OsStr::to_str)
+            .ok_or(SoundboardError::InvalidSound)?;
+
+        let mut cmd = Command::new(&self.ffmpeg_path);
+        cmd.args(["-f", extension]) // Input file format.
+            .args(["-i", "-"]) // Read from stdin.
+            .args(["-ar", &wav::FREQUENCY.to_string()]) // Resample to Discord voice frequency.
+            .args(["-ac", "1"]) // Downmix to mono.
+            .args(["-c:a", "pcm_s16le"])
+            .args(["-bitexact", "-map_metadata", "-1"]) // Drop metadata chunks.
+            .args(["-f", "wav"]) // Output to WAV.
+            .arg("-") // Output to stdout.
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        let mut child = cmd
+            .spawn()
+            .map_err(|_| SoundboardError::TranscodingFailed)?;
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or(SoundboardError::TranscodingFailed)?;
+        tokio::spawn(async move {
+            stdin
+                .write_all(&data)
+                .await
+                .expect("Failed to write sound to ffmpeg");
+        });
+
+        let out = child
+            .wait_with_output()
+            .await
+            .map_err(|_| SoundboardError::TranscodingFailed)?;
+        if !out.status.success() {
+            return Err(SoundboardError::TranscodingFailed);
+        }
+        // The output is streamed, so its header lacks the size of the data.
+        wav::normalize(&out.stdout).ok_or(SoundboardError::TranscodingFailed)
     }
 
     pub async fn delete(
@@ -352,39 +825,75 @@ impl Soundboard {
         name: &str,
         group: Option<&str>,
     ) -> Result<(), SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
+        let state = self.state.write().await;
+        let id = state.find(guild, name, group)?.metadata.id;
+        self.remove(state, id).await
+    }
 
-        let mut sounds = self.sounds.lock().await;
-        let mut matching = sounds.iter().filter_map(|(id, sound)| {
-            (sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true))
-            .then_some(*id)
-        });
-        let id = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
+    pub async fn delete_id(&self, guild: GuildId, id: Ulid) -> Result<(), SoundboardError> {
+        let state = self.state.write().await;
+        if !state.contains(guild, id) {
+            return Err(SoundboardError::SoundNotFound);
         }
+        self.remove(state, id).await
+    }
 
-        let sound = sounds.remove(&id).ok_or(SoundboardError::SoundNotFound)?;
-        self.overwrite_metadata_file(&sounds).await?;
-        fs::remove_file(sound.metadata.get_file_path(&self.sounds_dir_path))
-            .await
-            .map_err(|_| SoundboardError::DeleteFailed)
+    async fn remove(
+        &self,
+        mut state: RwLockWriteGuard<'_, State>,
+        id: Ulid,
+    ) -> Result<(), SoundboardError> {
+        let metadata = state
+            .remove(id)
+            .ok_or(SoundboardError::SoundNotFound)?
+            .metadata;
+        let file = metadata.file;
+
+        // Identical sounds of other guilds may still use the file.
+        let orphaned = !state.files.contains_key(&file);
+        if orphaned {
+            self.cache().remove(&file);
+        }
+        self.overwrite_metadata_file(state).await?;
+        if orphaned {
+            fs::remove_file(file_path(&self.sounds_dir_path, file))
+                .await
+                .map_err(|_| SoundboardError::DeleteFailed)?;
+        }
+        self.events.publish(
+            GuildId::new(metadata.guild),
+            GuildEvent::SoundRemoved {
+                id,
+                name: metadata.name,
+                group: metadata.group,
+            },
+        );
+        Ok(())
     }
 
-    pub async fn delete_id(&self, guild: GuildId, id: Ulid) -> Result<(), SoundboardError> {
-        let mut sounds = self.sounds.lock().await;
-        let sound = sounds.remove(&id).ok_or(SoundboardError::SoundNotFound)?;
-        assert_eq!(sound.metadata.guild, guild.get());
-        self.overwrite_metadata_file(&sounds).await?;
-        fs::remove_file(sound.metadata.get_file_path(&self.sounds_dir_path))
-            .await
-            .map_err(|_| SoundboardError::DeleteFailed)
+    /// Write the whole metadata file after a change of one sound, and let
+    /// subscribers know about it.
+    async fn overwrite_updated(
+        &self,
+        state: RwLockWriteGuard<'_, State>,
+        id: Ulid,
+    ) -> Result<(), SoundboardError> {
+        let metadata = state
+            .sounds
+            .get(&id)
+            .ok_or(SoundboardError::SoundNotFound)?
+            .metadata
+            .clone();
+        self.overwrite_metadata_file(state).await?;
+        self.events.publish(
+            GuildId::new(metadata.guild),
+            GuildEvent::SoundUpdated {
+                id,
+                name: metadata.name,
+                group: metadata.group,
+            },
+        );
+        Ok(())
     }
 
     pub async fn rename(
@@ -394,47 +903,32 @@ impl Soundboard {
         group: Option<&str>,
         new_name: String,
     ) -> Result<bool, SoundboardError> {
-        let name_regex = match_regex(name);
+        let mut state = self.state.write().await;
+
+        // Get sound id. We must resolve ambiguity first.
+        let sound = state.find(guild, name, group)?;
+        let (id, group) = (sound.metadata.id, sound.metadata.group.clone());
 
         // Check if it's the same name.
-        if name_regex.is_match(&new_name) {
+        if match_regex(&sound.metadata.name).is_match(&new_name) {
             return Ok(false);
         }
 
-        let group_regex = group.map(match_regex);
-        let mut sounds = self.sounds.lock().await;
-
-        // Get sound id. We must resolve ambiguity first.
-        let mut matching = sounds.values().filter_map(|sound| {
-            (sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true))
-            .then(|| (sound.metadata.id, sound.metadata.group.clone()))
-        });
-        let (id, group) = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
-
         // Check if a sound with the requested name already exists.
         let new_name_regex = match_regex(&new_name);
-        if sounds.values().any(|sound| {
-            sound.metadata.guild == guild.get()
-                && new_name_regex.is_match(&sound.metadata.name)
-                && sound.metadata.group == group
+        if state.guild_sounds(guild).any(|sound| {
+            new_name_regex.is_match(&sound.metadata.name) && sound.metadata.group == group
         }) {
             return Err(SoundboardError::NameTaken);
         }
 
-        sounds
+        state
+            .sounds
             .get_mut(&id)
             .ok_or(SoundboardError::SoundNotFound)?
             .metadata
             .name = new_name;
-        self.overwrite_metadata_file(&sounds).await?;
+        self.overwrite_updated(state, id).await?;
         Ok(true)
     }
 
@@ -443,95 +937,60 @@ impl Soundboard {
         guild: GuildId,
         name: &str,
         group: Option<&str>,
-        mut new_group: String,
+        new_group: String,
     ) -> Result<bool, SoundboardError> {
-        let name_regex = match_regex(name);
         let new_group_regex = match_regex(&new_group);
-        let mut sounds = self.sounds.lock().await;
-
-        // Check if the name is already taken in the target group.
-        if sounds.values().any(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && new_group_regex.is_match(&sound.metadata.group)
-        }) {
-            return Err(SoundboardError::NameTaken);
-        }
-
-        // Find similar existing group.
-        new_group = sounds
-            .values()
-            .find_map(|sound| {
-                new_group_regex
-                    .is_match(&sound.metadata.group)
-                    .then(|| sound.metadata.group.clone())
-            })
-            .unwrap_or(new_group);
-
-        // Find new position.
-        let index = sounds
-            .values()
-            .filter_map(|s| {
-                (s.metadata.guild == guild.get() && s.metadata.group == new_group)
-                    .then_some(s.metadata.index)
-            })
-            .max()
-            .map(|i| i + 1)
-            .unwrap_or(0);
+        let mut state = self.state.write().await;
 
         // Find requested sound to change.
-        let group_regex = group.map(match_regex);
-        let mut matching = sounds.values_mut().filter(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true)
-        });
-        let sound = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
+        let sound = state.find(guild, name, group)?;
+        let id = sound.metadata.id;
 
         // Check if old and new groups are the same.
         if new_group_regex.is_match(&sound.metadata.group) {
             return Ok(false);
         }
 
+        // Check if the name is already taken in the target group.
+        let name_regex = match_regex(&sound.metadata.name);
+        if state.guild_sounds(guild).any(|sound| {
+            name_regex.is_match(&sound.metadata.name)
+                && new_group_regex.is_match(&sound.metadata.group)
+        }) {
+            return Err(SoundboardError::NameTaken);
+        }
+
+        // Find similar existing group and new position.
+        let new_group = state.similar_group(guild, new_group);
+        let index = state.next_index(guild, &new_group);
+
+        let sound = state
+            .sounds
+            .get_mut(&id)
+            .ok_or(SoundboardError::SoundNotFound)?;
         sound.metadata.group = new_group;
         sound.metadata.index = index;
-        self.overwrite_metadata_file(&sounds).await?;
+        self.overwrite_updated(state, id).await?;
         Ok(true)
     }
 
-    async fn change_sound_field<R, F: FnOnce(&mut Sound) -> (R, bool)>(
+    async fn change_sound_field<R, F: FnOnce(&mut SoundMetadata) -> (R, bool)>(
         &self,
         guild: GuildId,
         name: &str,
         group: Option<&str>,
         f: F,
     ) -> Result<R, SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
-
-        let mut sounds = self.sounds.lock().await;
-        let mut matching = sounds.values_mut().filter(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true)
-        });
-        let sound = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
+        let mut state = self.state.write().await;
+        let id = state.find(guild, name, group)?.metadata.id;
+        let sound = state
+            .sounds
+            .get_mut(&id)
+            .ok_or(SoundboardError::SoundNotFound)?;
 
-        let (res, overwrite) = f(sound);
+        let (res, overwrite) = f(&mut sound.metadata);
         if overwrite {
-            self.overwrite_metadata_file(&sounds).await?;
+            self.overwrite_updated(state, id).await?;
         }
         Ok(res)
     }
@@ -543,11 +1002,11 @@ impl Soundboard {
         group: Option<&str>,
         color: ButtonStyle,
     ) -> Result<bool, SoundboardError> {
-        self.change_sound_field(guild, name, group, |s| {
-            if s.metadata.color == color {
+        self.change_sound_field(guild, name, group, |metadata| {
+            if metadata.color == color {
                 (false, false)
             } else {
-                s.metadata.color = color;
+                metadata.color = color;
                 (true, true)
             }
         })
@@ -561,142 +1020,375 @@ impl Soundboard {
         group: Option<&str>,
         emoji: String,
     ) -> Result<bool, SoundboardError> {
-        self.change_sound_field(guild, name, group, |s| {
-            if s.metadata.emoji.as_ref() == Some(&emoji) {
+        self.change_sound_field(guild, name, group, |metadata| {
+            if metadata.emoji.as_ref() == Some(&emoji) {
                 (false, false)
             } else {
-                s.metadata.emoji = Some(emoji);
+                metadata.emoji = Some(emoji);
                 (true, true)
             }
         })
         .await
     }
 
-    pub async fn get_id(
+    /// Apply several changes to a sound at once, with a single metadata write.
+    /// Returns the updated sound and whether anything changed.
+    pub async fn update(
+        &self,
+        guild: GuildId,
+        id: Ulid,
+        update: SoundUpdate,
+    ) -> Result<(SoundDetails, bool), SoundboardError> {
+        let mut state = self.state.write().await;
+        if !state.contains(guild, id) {
+            return Err(SoundboardError::SoundNotFound);
+        }
+        let changed = state.apply_update(guild, id, update)?;
+
+        let details = state
+            .sounds
+            .get(&id)
+            .ok_or(SoundboardError::SoundNotFound)?
+            .details();
+        if changed {
+            self.overwrite_updated(state, id).await?;
+        }
+        Ok((details, changed))
+    }
+
+    /// Groups of a guild having sounds, in display order, with their settings.
+    pub async fn groups(&self, guild: GuildId) -> Vec<GroupMetadata> {
+        let state = self.state.read().await;
+        state
+            .guild_groups(guild)
+            .into_iter()
+            .map(|name| {
+                state
+                    .group_settings(guild, &name)
+                    .cloned()
+                    .unwrap_or_else(|| GroupMetadata::new(name))
+            })
+            .collect()
+    }
+
+    /// Existing spelling of a group of a guild, matched case-insensitively.
+    pub async fn find_group(&self, guild: GuildId, group: &str) -> Result<String, SoundboardError> {
+        self.state.read().await.find_group(guild, group)
+    }
+
+    /// Color of a sound uploaded without one: the color of its group if it has
+    /// one, or derived from its name.
+    pub async fn default_color(
         &self,
         guild: GuildId,
+        group: &str,
         name: &str,
-        group: Option<&str>,
-    ) -> Result<Ulid, SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
+        with_grey: bool,
+    ) -> ButtonStyle {
+        let state = self.state.read().await;
+        state
+            .find_group(guild, group)
+            .ok()
+            .and_then(|group| state.group_settings(guild, &group)?.color)
+            .unwrap_or_else(|| button::determinist(&name.to_lowercase(), with_grey))
+    }
 
-        let sounds = self.sounds.lock().await;
-        let mut matching = sounds.iter().filter_map(|(id, sound)| {
-            (sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true))
-            .then_some(*id)
-        });
-        let id = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
+    /// Move a group to a position of the display order of its guild. Returns
+    /// whether it moved.
+    pub async fn reorder_group(
+        &self,
+        guild: GuildId,
+        group: &str,
+        position: usize,
+    ) -> Result<bool, SoundboardError> {
+        let mut state = self.state.write().await;
+        let group = state.find_group(guild, group)?;
+        let groups = state.ordered_groups(guild);
+        let current = groups
+            .iter()
+            .position(|g| g.name == group)
+            .ok_or(SoundboardError::GroupNotFound)?;
+        if current == position.min(groups.len() - 1) {
+            return Ok(false);
         }
 
-        Ok(id)
+        let moved = groups.remove(current);
+        groups.insert(position.min(groups.len()), moved);
+        self.overwrite_groups_file(state).await?;
+        self.events
+            .publish(guild, GuildEvent::GroupUpdated { group });
+        Ok(true)
+    }
+
+    /// Replace the settings of a group. Returns whether they changed.
+    pub async fn change_group_settings(
+        &self,
+        guild: GuildId,
+        group: &str,
+        color: Option<ButtonStyle>,
+        emoji: Option<String>,
+        description: Option<String>,
+    ) -> Result<bool, SoundboardError> {
+        let mut state = self.state.write().await;
+        let group = state.find_group(guild, group)?;
+        let settings = state
+            .ordered_groups(guild)
+            .iter_mut()
+            .find(|g| g.name == group)
+            .ok_or(SoundboardError::GroupNotFound)?;
+        let changed = settings.color != color
+            || settings.emoji != emoji
+            || settings.description != description;
+        if !changed {
+            return Ok(false);
+        }
+
+        settings.color = color;
+        settings.emoji = emoji;
+        settings.description = description;
+        self.overwrite_groups_file(state).await?;
+        self.events
+            .publish(guild, GuildEvent::GroupUpdated { group });
+        Ok(true)
+    }
+
+    /// Rename a group and move all its sounds to it, keeping its settings.
+    /// Returns whether the name changed.
+    pub async fn rename_group(
+        &self,
+        guild: GuildId,
+        group: &str,
+        new_name: String,
+    ) -> Result<bool, SoundboardError> {
+        let new_name = new_name.trim().to_owned();
+        if new_name.is_empty() {
+            return Err(SoundboardError::EmptyGroupName);
+        }
+        let mut state = self.state.write().await;
+        let group = state.find_group(guild, group)?;
+        if group == new_name {
+            return Ok(false);
+        }
+        // Only the case of the name may change without merging groups.
+        if state
+            .find_group(guild, &new_name)
+            .is_ok_and(|existing| existing != group)
+        {
+            return Err(SoundboardError::GroupNameTaken);
+        }
+
+        if let Some(settings) = state
+            .ordered_groups(guild)
+            .iter_mut()
+            .find(|g| g.name == group)
+        {
+            settings.name = new_name.clone();
+        }
+        let ids = state
+            .group_sounds(guild, &group)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect_vec();
+        for id in &ids {
+            if let Some(sound) = state.sounds.get_mut(id) {
+                sound.metadata.group = new_name.clone();
+            }
+        }
+        let groups = serde_json::to_vec(&state.groups).map_err(|_| SoundboardError::SoundWrite)?;
+        self.overwrite_metadata_file(state).await?;
+        self.write_groups_file(groups).await?;
+        self.events.publish(
+            guild,
+            GuildEvent::GroupUpdated {
+                group: new_name.clone(),
+            },
+        );
+        Ok(true)
+    }
+
+    /// Delete a group along with all its sounds. Returns the number of sounds
+    /// deleted.
+    pub async fn delete_group(
+        &self,
+        guild: GuildId,
+        group: &str,
+    ) -> Result<usize, SoundboardError> {
+        let mut state = self.state.write().await;
+        let group = state.find_group(guild, group)?;
+        let mut removed = Vec::new();
+        for (_, id) in state.group_sounds(guild, &group) {
+            if let Some(sound) = state.remove(id) {
+                removed.push(sound.metadata);
+            }
+        }
+        if let Some(groups) = state.groups.get_mut(&guild) {
+            groups.retain(|g| g.name != group);
+        }
+
+        // Identical sounds of other guilds may still use the files.
+        let orphaned = removed
+            .iter()
+            .map(|metadata| metadata.file)
+            .filter(|file| !state.files.contains_key(file))
+            .unique()
+            .collect_vec();
+        for file in &orphaned {
+            self.cache().remove(file);
+        }
+        let groups = serde_json::to_vec(&state.groups).map_err(|_| SoundboardError::SoundWrite)?;
+        self.overwrite_metadata_file(state).await?;
+        self.write_groups_file(groups).await?;
+        for file in orphaned {
+            fs::remove_file(file_path(&self.sounds_dir_path, file))
+                .await
+                .map_err(|_| SoundboardError::DeleteFailed)?;
+        }
+
+        let count = removed.len();
+        for metadata in removed {
+            self.events.publish(
+                guild,
+                GuildEvent::SoundRemoved {
+                    id: metadata.id,
+                    name: metadata.name,
+                    group: metadata.group,
+                },
+            );
+        }
+        Ok(count)
+    }
+
+    pub async fn get_id(
+        &self,
+        guild: GuildId,
+        name: &str,
+        group: Option<&str>,
+    ) -> Result<Ulid, SoundboardError> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .find(guild, name, group)?
+            .metadata
+            .id)
     }
 
     pub async fn random_id(&self, guild: GuildId) -> Option<Ulid> {
-        self.sounds
-            .lock()
+        self.state
+            .read()
             .await
-            .values()
-            .filter_map(|sound| (sound.metadata.guild == guild.get()).then_some(sound.metadata.id))
+            .guild_sounds(guild)
+            .map(|sound| sound.metadata.id)
             .choose(&mut rand::rng())
     }
 
     pub async fn random_id_in_group(&self, guild: GuildId, group_hash: u64) -> Option<Ulid> {
-        self.sounds
-            .lock()
+        self.state
+            .read()
             .await
-            .values()
-            .filter_map(|sound| {
-                if sound.metadata.guild != guild.get() {
-                    return None;
-                }
+            .guild_sounds(guild)
+            .filter(|sound| {
                 let mut hasher = DefaultHasher::new();
                 sound.metadata.group.hash(&mut hasher);
-                (hasher.finish() == group_hash).then_some(sound.metadata.id)
+                hasher.finish() == group_hash
             })
+            .map(|sound| sound.metadata.id)
             .choose(&mut rand::rng())
     }
 
     pub async fn latest_id(&self, guild: GuildId) -> Option<Ulid> {
-        self.sounds
-            .lock()
+        self.state
+            .read()
             .await
-            .values()
-            .filter_map(|sound| (sound.metadata.guild == guild.get()).then_some(sound.metadata.id))
+            .guild_sounds(guild)
+            .map(|sound| sound.metadata.id)
             .max()
     }
 
+    /// Serialize the whole state and write it once the state lock has been
+    /// released.
     async fn overwrite_metadata_file(
         &self,
-        sounds: &HashMap<Ulid, Sound>,
+        state: RwLockWriteGuard<'_, State>,
     ) -> Result<(), SoundboardError> {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.metadata_path)
-            .await
+        let data = store::encode_metadata(state.sounds.values().map(|sound| &sound.metadata))
             .map_err(|_| SoundboardError::SoundWrite)?;
 
-        for sound in sounds.values() {
-            file.write(
-                &bincode::serialize(&sound.metadata).map_err(|_| SoundboardError::SoundWrite)?,
-            )
+        let _file_guard = self.metadata_file.lock().await;
+        drop(state);
+        fs::write(&self.metadata_path, data)
             .await
-            .map_err(|_| SoundboardError::SoundWrite)?;
-        }
+            .map_err(|_| SoundboardError::SoundWrite)
+    }
 
-        Ok(())
+    async fn overwrite_groups_file(
+        &self,
+        state: RwLockWriteGuard<'_, State>,
+    ) -> Result<(), SoundboardError> {
+        let data = serde_json::to_vec(&state.groups).map_err(|_| SoundboardError::SoundWrite)?;
+        let _file_guard = self.groups_file.lock().await;
+        drop(state);
+        fs::write(&self.groups_path, data)
+            .await
+            .map_err(|_| SoundboardError::SoundWrite)
+    }
+
+    /// Write groups serialized along with a change of the sounds, after the
+    /// metadata file.
+    async fn write_groups_file(&self, data: Vec<u8>) -> Result<(), SoundboardError> {
+        let _file_guard = self.groups_file.lock().await;
+        fs::write(&self.groups_path, data)
+            .await
+            .map_err(|_| SoundboardError::SoundWrite)
+    }
+
+    async fn overwrite_plays_file(&self) -> Result<(), SoundboardError> {
+        let plays = self
+            .state
+            .read()
+            .await
+            .sounds
+            .values()
+            .map(|sound| (sound.metadata.id, sound.plays.load(Ordering::Relaxed)))
+            .filter(|(_id, plays)| *plays > 0)
+            .collect::<HashMap<_, _>>();
+        fs::write(
+            &self.plays_path,
+            bincode::serialize(&plays).map_err(|_| SoundboardError::SoundWrite)?,
+        )
+        .await
+        .map_err(|_| SoundboardError::SoundWrite)
+    }
+
+    fn cache(&self) -> SyncMutexGuard<'_, SoundCache> {
+        self.cache.lock().expect("Sound cache lock poisoned")
+    }
+
+    /// Read a WAV file, possibly shared by several sounds. The cache only holds
+    /// decoded samples, so this always hits the disk.
+    async fn read_wav(&self, file: Ulid) -> Option<Vec<u8>> {
+        fs::read(file_path(&self.sounds_dir_path, file)).await.ok()
     }
 
     pub async fn backup(
         &self,
         guild: GuildId,
     ) -> Result<(String, Vec<(String, Vec<u8>)>), SoundboardError> {
-        let mut sounds = self.sounds.lock().await;
-
-        let metadata = sounds
-            .values()
-            .filter(|sound| sound.metadata.guild == guild.get())
-            .into_group_map_by(|sound| &sound.metadata.group)
-            .into_iter()
-            .sorted_by(|(g1, _), (g2, _)| g1.cmp(g2))
-            .map(|(group, mut sounds)| {
-                sounds.sort_by_key(|s| s.metadata.index);
-                let sounds = sounds
-                    .into_iter()
-                    .map(|sound| {
-                        json!({
-                            "id": sound.metadata.id.to_string(),
-                            "name": sound.metadata.name,
-                            "emoji": sound.metadata.emoji,
-                            "color": button::as_str(sound.metadata.color),
-                        })
-                    })
-                    .collect::<Value>();
-                json!({
-                    "group": group,
-                    "sounds": sounds,
-                })
-            })
-            .collect::<Value>();
+        let (metadata, ids) = {
+            let state = self.state.read().await;
+            let metadata = state.backup_groups(guild);
+            let ids = state
+                .guild_sounds(guild)
+                .map(|sound| (sound.metadata.id, sound.metadata.file))
+                .collect_vec();
+            (metadata, ids)
+        };
 
-        let mut data = Vec::new();
-        for sound in sounds
-            .values_mut()
-            .filter(|sound| sound.metadata.guild == guild.get())
-        {
+        let mut data = Vec::with_capacity(ids.len());
+        for (id, file) in ids {
             data.push((
-                format!("{}.wav", sound.metadata.id.to_string()),
-                sound
-                    .get_wav_data(&self.sounds_dir_path, false)
+                store::backup_file_name(id),
+                self.read_wav(file)
                     .await
                     .ok_or(SoundboardError::BackupFailed)?,
             ));
@@ -709,56 +1401,407 @@ impl Soundboard {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct SoundMetadata {
-    guild: u64,
-    pub id: Ulid,
-    pub name: String,
-    pub emoji: Option<String>,
-    pub color: ButtonStyle,
-    group: String,
-    index: usize,
+/// Every sound of every guild, plus an index of sounds per guild so lookups
+/// never scan other guilds, the number of sounds using each file and the
+/// order of the groups of each guild.
+#[derive(Default, Debug)]
+struct State {
+    sounds: HashMap<Ulid, Sound>,
+    guilds: HashMap<GuildId, HashSet<Ulid>>,
+    files: HashMap<Ulid, usize>,
+    groups: HashMap<GuildId, Vec<GroupMetadata>>,
 }
 
-impl SoundMetadata {
-    fn get_file_path(&self, dir_path: &Path) -> PathBuf {
-        let mut path = dir_path.join(self.id.to_string());
-        path.set_extension("wav");
-        path
+impl State {
+    fn insert(&mut self, sound: Sound) {
+        self.guilds
+            .entry(GuildId::new(sound.metadata.guild))
+            .or_default()
+            .insert(sound.metadata.id);
+        *self.files.entry(sound.metadata.file).or_default() += 1;
+        self.sounds.insert(sound.metadata.id, sound);
+    }
+
+    fn remove(&mut self, id: Ulid) -> Option<Sound> {
+        let sound = self.sounds.remove(&id)?;
+        let guild = GuildId::new(sound.metadata.guild);
+        if let Some(ids) = self.guilds.get_mut(&guild) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.guilds.remove(&guild);
+            }
+        }
+        if let Some(references) = self.files.get_mut(&sound.metadata.file) {
+            *references -= 1;
+            if *references == 0 {
+                self.files.remove(&sound.metadata.file);
+            }
+        }
+        Some(sound)
+    }
+
+    fn contains(&self, guild: GuildId, id: Ulid) -> bool {
+        self.guilds.get(&guild).is_some_and(|ids| ids.contains(&id))
+    }
+
+    fn guild_sounds(&self, guild: GuildId) -> impl Iterator<Item = &Sound> {
+        self.guilds
+            .get(&guild)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.sounds.get(id))
+    }
+
+    /// Find a sound of the guild with the exact same content.
+    fn guild_duplicate(&self, guild: GuildId, hash: [u8; 32]) -> Option<&Sound> {
+        self.guild_sounds(guild)
+            .find(|sound| sound.metadata.hash == Some(hash))
+    }
+
+    /// Find the file of a sound with the exact same content, in any guild.
+    fn file_with_hash(&self, hash: [u8; 32]) -> Option<Ulid> {
+        self.sounds
+            .values()
+            .find(|sound| sound.metadata.hash == Some(hash))
+            .map(|sound| sound.metadata.file)
+    }
+
+    /// Find a unique sound by its name and optionally by its group, or by its
+    /// id as sent by autocompletion.
+    fn find(
+        &self,
+        guild: GuildId,
+        name: &str,
+        group: Option<&str>,
+    ) -> Result<&Sound, SoundboardError> {
+        if let Ok(id) = Ulid::from_string(name)
+            && self.contains(guild, id)
+        {
+            return self.sounds.get(&id).ok_or(SoundboardError::SoundNotFound);
+        }
+
+        let name_regex = match_regex(name);
+        let group_regex = group.map(match_regex);
+
+        let mut matching = self.guild_sounds(guild).filter(|sound| {
+            name_regex.is_match(&sound.metadata.name)
+                && group_regex
+                    .as_ref()
+                    .map(|rg| rg.is_match(&sound.metadata.group))
+                    .unwrap_or(true)
+        });
+        let sound = matching.next().ok_or(SoundboardError::SoundNotFound)?;
+        if matching.next().is_some() {
+            return Err(SoundboardError::SoundNameAmbiguous);
+        }
+        Ok(sound)
+    }
+
+    /// Reuse the spelling of an existing group if one matches
+    /// case-insensitively.
+    fn similar_group(&self, guild: GuildId, group: String) -> String {
+        let group_regex = match_regex(&group);
+        self.guild_sounds(guild)
+            .find(|sound| group_regex.is_match(&sound.metadata.group))
+            .map(|sound| sound.metadata.group.clone())
+            .unwrap_or(group)
+    }
+
+    /// Sort key of a group, ordered groups first then the others by name.
+    fn group_rank<'a>(&self, guild: GuildId, group: &'a str) -> (usize, &'a str) {
+        let position = self
+            .groups
+            .get(&guild)
+            .and_then(|groups| groups.iter().position(|g| g.name == group));
+        (position.unwrap_or(usize::MAX), group)
+    }
+
+    /// Existing spelling of a group, matched case-insensitively.
+    fn find_group(&self, guild: GuildId, group: &str) -> Result<String, SoundboardError> {
+        let group_regex = match_regex(group);
+        self.guild_sounds(guild)
+            .find(|sound| group_regex.is_match(&sound.metadata.group))
+            .map(|sound| sound.metadata.group.clone())
+            .ok_or(SoundboardError::GroupNotFound)
+    }
+
+    fn group_settings(&self, guild: GuildId, group: &str) -> Option<&GroupMetadata> {
+        self.groups.get(&guild)?.iter().find(|g| g.name == group)
+    }
+
+    /// Store every group of a guild having sounds, in display order, to edit
+    /// one without changing the order of the others. Settings of groups left
+    /// without sounds are dropped.
+    fn ordered_groups(&mut self, guild: GuildId) -> &mut Vec<GroupMetadata> {
+        let order = self.guild_groups(guild);
+        let mut previous = self.groups.remove(&guild).unwrap_or_default();
+        let groups = order
+            .into_iter()
+            .map(|name| match previous.iter().position(|g| g.name == name) {
+                Some(index) => previous.swap_remove(index),
+                None => GroupMetadata::new(name),
+            })
+            .collect();
+        self.groups.entry(guild).or_insert(groups)
+    }
+
+    /// Groups of a guild having sounds, in display order.
+    fn guild_groups(&self, guild: GuildId) -> Vec<String> {
+        self.guild_sounds(guild)
+            .map(|sound| sound.metadata.group.as_str())
+            .unique()
+            .sorted_by(|g1, g2| self.group_rank(guild, g1).cmp(&self.group_rank(guild, g2)))
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Groups of a guild and their sounds as saved in backups, in display
+    /// order.
+    fn backup_groups(&self, guild: GuildId) -> Vec<BackupGroup> {
+        self.guild_groups(guild)
+            .into_iter()
+            .map(|group| BackupGroup {
+                sounds: self
+                    .group_sounds(guild, &group)
+                    .into_iter()
+                    .filter_map(|(_, id)| self.sounds.get(&id))
+                    .map(|sound| BackupSound {
+                        id: sound.metadata.id,
+                        name: sound.metadata.name.clone(),
+                        emoji: sound.metadata.emoji.clone(),
+                        color: button::as_str(sound.metadata.color).to_owned(),
+                    })
+                    .collect(),
+                group,
+            })
+            .collect()
+    }
+
+    /// Sounds of a group, in display order.
+    fn group_sounds(&self, guild: GuildId, group: &str) -> Vec<(usize, Ulid)> {
+        self.guild_sounds(guild)
+            .filter(|s| s.metadata.group == group)
+            .map(|s| (s.metadata.index, s.metadata.id))
+            .sorted()
+            .collect()
+    }
+
+    /// Index placing a sound at a position of a group, ignoring the sound
+    /// being moved, halfway between its new neighbours. The sounds of the group
+    /// are spread out again when there is no room left between them, which is
+    /// the only case where other sounds change, returned as `true`.
+    fn index_at(
+        &mut self,
+        guild: GuildId,
+        group: &str,
+        position: Option<usize>,
+        moving: Option<Ulid>,
+    ) -> (usize, bool) {
+        let sounds = self
+            .group_sounds(guild, group)
+            .into_iter()
+            .filter(|(_, id)| Some(*id) != moving)
+            .collect_vec();
+        let position = position.unwrap_or(sounds.len()).min(sounds.len());
+        let before = position.checked_sub(1).map(|p| sounds[p].0);
+        let after = sounds.get(position).map(|(index, _)| *index);
+
+        match (before, after) {
+            (None, None) => return (INDEX_GAP, false),
+            (Some(before), None) => return (before + INDEX_GAP, false),
+            (None, Some(after)) if after >= 2 => return (after / 2, false),
+            (Some(before), Some(after)) if after - before >= 2 => {
+                return (before + (after - before) / 2, false);
+            }
+            _ => {}
+        }
+
+        for (i, (_, id)) in sounds.iter().enumerate() {
+            let rank = if i < position { i + 1 } else { i + 2 };
+            if let Some(sound) = self.sounds.get_mut(id) {
+                sound.metadata.index = rank * INDEX_GAP;
+            }
+        }
+        ((position + 1) * INDEX_GAP, true)
+    }
+
+    /// Apply an update to a sound of the guild. Returns whether it changed.
+    fn apply_update(
+        &mut self,
+        guild: GuildId,
+        id: Ulid,
+        update: SoundUpdate,
+    ) -> Result<bool, SoundboardError> {
+        let current = self
+            .sounds
+            .get(&id)
+            .ok_or(SoundboardError::SoundNotFound)?
+            .metadata
+            .clone();
+
+        // Moving to a group that only differs by its case is not a move.
+        let new_group = update
+            .group
+            .filter(|group| !match_regex(group).is_match(&current.group))
+            .map(|group| self.similar_group(guild, group));
+        let new_name = update.name.filter(|name| *name != current.name);
+
+        // Check if the name is already taken in the target group.
+        if new_name.is_some() || new_group.is_some() {
+            let name_regex = match_regex(new_name.as_ref().unwrap_or(&current.name));
+            let group = new_group.as_ref().unwrap_or(&current.group);
+            if self.guild_sounds(guild).any(|sound| {
+                sound.metadata.id != id
+                    && name_regex.is_match(&sound.metadata.name)
+                    && sound.metadata.group == *group
+            }) {
+                return Err(SoundboardError::NameTaken);
+            }
+        }
+        // Append the sound to its new group, its index being meaningless there.
+        let new_index = new_group
+            .as_ref()
+            .map(|group| self.next_index(guild, group));
+
+        let sound = self
+            .sounds
+            .get_mut(&id)
+            .ok_or(SoundboardError::SoundNotFound)?;
+        let metadata = &mut sound.metadata;
+        if let Some(name) = new_name {
+            metadata.name = name;
+        }
+        if let (Some(group), Some(index)) = (new_group, new_index) {
+            metadata.group = group;
+            metadata.index = index;
+        }
+        if let Some(emoji) = update.emoji {
+            metadata.emoji = emoji;
+        }
+        if let Some(color) = update.color {
+            metadata.color = color;
+        }
+        let mut changed = metadata.name != current.name
+            || metadata.group != current.group
+            || metadata.emoji != current.emoji
+            || metadata.color != current.color;
+        if let Some(position) = update.position {
+            changed |= self.reposition(guild, id, position);
+        }
+        Ok(changed)
+    }
+
+    /// Move a sound to a position of its group. Returns whether it moved.
+    fn reposition(&mut self, guild: GuildId, id: Ulid, position: usize) -> bool {
+        let Some(group) = self.sounds.get(&id).map(|s| s.metadata.group.clone()) else {
+            return false;
+        };
+        let sounds = self.group_sounds(guild, &group);
+        let current = sounds.iter().position(|(_, sound)| *sound == id);
+        if current == Some(position.min(sounds.len() - 1)) {
+            return false;
+        }
+
+        let (index, _) = self.index_at(guild, &group, Some(position), Some(id));
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.metadata.index = index;
+        }
+        true
+    }
+
+    fn next_index(&mut self, guild: GuildId, group: &str) -> usize {
+        self.index_at(guild, group, None, None).0
+    }
+
+    /// Validate a new sound and resolve its group and position. Returns the
+    /// metadata to insert, using its own file and no content hash, and whether
+    /// other sounds were shifted.
+    #[allow(clippy::too_many_arguments)]
+    fn prepare_insert(
+        &mut self,
+        guild: GuildId,
+        id: Ulid,
+        name: String,
+        emoji: Option<String>,
+        color: ButtonStyle,
+        group: String,
+        requested_index: Option<usize>,
+    ) -> Result<(SoundMetadata, bool), SoundboardError> {
+        // Find similar existing group.
+        let group = self.similar_group(guild, group);
+
+        // Check if name is already taken in this group.
+        let name_regex = match_regex(&name);
+        if self
+            .guild_sounds(guild)
+            .any(|sound| name_regex.is_match(&sound.metadata.name) && sound.metadata.group == group)
+        {
+            return Err(SoundboardError::NameTaken);
+        }
+
+        // Resolve index position.
+        let (index, overwrite_required) = self.index_at(guild, &group, requested_index, None);
+
+        Ok((
+            SoundMetadata {
+                guild: guild.get(),
+                id,
+                file: id,
+                hash: None,
+                name,
+                emoji,
+                color,
+                group,
+                index,
+            },
+            overwrite_required,
+        ))
     }
 }
 
 #[derive(Debug)]
 struct Sound {
     metadata: SoundMetadata,
-    data: CachedSound,
-}
-
-#[derive(Debug)]
-enum CachedSound {
-    Fs,
-    Cached(Vec<u8>, Instant),
+    plays: AtomicU64,
+    duration: Duration,
 }
 
 impl Sound {
-    async fn get_wav_data(&mut self, dir_path: &Path, cache: bool) -> Option<Vec<u8>> {
-        match &mut self.data {
-            CachedSound::Fs => {
-                let path = self.metadata.get_file_path(dir_path);
-                let data = fs::read(&path).await.ok()?;
-                if cache {
-                    self.data = CachedSound::Cached(data.clone(), Instant::now());
-                }
-                Some(data)
-            }
-            CachedSound::Cached(data, fetched) => {
-                if cache {
-                    *fetched = Instant::now();
-                }
-                Some(data.clone())
-            }
+    fn new(metadata: SoundMetadata, plays: u64, duration: Duration) -> Self {
+        Self {
+            metadata,
+            plays: AtomicU64::new(plays),
+            duration,
         }
     }
+
+    fn details(&self) -> SoundDetails {
+        SoundDetails {
+            metadata: self.metadata.clone(),
+            duration: self.duration,
+            plays: self.plays.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Changes to apply to a sound, `None` fields are left untouched.
+#[derive(Default, Debug)]
+pub struct SoundUpdate {
+    pub name: Option<String>,
+    pub group: Option<String>,
+    /// `Some(None)` removes the emoji.
+    pub emoji: Option<Option<String>>,
+    pub color: Option<ButtonStyle>,
+    /// Position in its group, after moving it if it changes group.
+    pub position: Option<usize>,
+}
+
+/// Metadata of a sound along with what is only known at runtime.
+#[derive(Clone, Debug)]
+pub struct SoundDetails {
+    pub metadata: SoundMetadata,
+    pub duration: Duration,
+    pub plays: u64,
 }
 
 #[derive(ThisError, Debug)]
@@ -777,12 +1820,24 @@ pub enum SoundboardError {
     SoundWrite,
     #[error("Cannot find that sound.")]
     SoundNotFound,
+    #[error("Cannot find that group.")]
+    GroupNotFound,
+    #[error("A group with this name already exists.")]
+    GroupNameTaken,
     #[error("Sound name is ambiguous. Try to add a group too.")]
     SoundNameAmbiguous,
     #[error("Failed to delete sound.")]
     DeleteFailed,
     #[error("Failed to create backup.")]
     BackupFailed,
+    #[error("This sound is identical to \"{0}\".")]
+    DuplicateSound(String),
+    #[error("Cannot read this ZIP archive.")]
+    InvalidArchive,
+    #[error("No group given, neither as an option nor as a folder of the archive.")]
+    MissingGroup,
+    #[error("Group name cannot be empty.")]
+    EmptyGroupName,
 }
 
 fn match_regex(searching: &str) -> Regex {
@@ -792,3 +1847,262 @@ fn match_regex(searching: &str) -> Regex {
 fn search_regex(searching: &str) -> Regex {
     Regex::new(&format!("(?i){}", regex::escape(searching))).expect("Failed to build search regex")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use itertools::Itertools;
+    use serenity::all::{ButtonStyle, GuildId};
+    use ulid::Ulid;
+
+    use super::{
+        GroupMetadata, INDEX_GAP, Sound, SoundMetadata, SoundUpdate, SoundboardError, State,
+    };
+
+    fn add(state: &mut State, guild: u64, name: &str, group: &str, index: Option<usize>) -> Ulid {
+        let id = Ulid::new();
+        let (metadata, _) = state
+            .prepare_insert(
+                GuildId::new(guild),
+                id,
+                name.to_owned(),
+                None,
+                ButtonStyle::Primary,
+                group.to_owned(),
+                index,
+            )
+            .expect("Failed to prepare sound");
+        state.insert(Sound::new(metadata, 0, Duration::ZERO));
+        id
+    }
+
+    #[test]
+    fn guilds_isolation() {
+        let mut state = State::default();
+        let first = add(&mut state, 1, "Hello", "Greetings", None);
+        let second = add(&mut state, 2, "hello", "greetings", None);
+
+        // Groups are only merged within the same guild.
+        assert_eq!(state.sounds[&second].metadata.group, "greetings");
+        assert_eq!(
+            state
+                .find(GuildId::new(1), "HELLO", None)
+                .map(|s| s.metadata.id)
+                .ok(),
+            Some(first)
+        );
+
+        // Ids from autocompletion only find sounds of their own guild.
+        assert_eq!(
+            state
+                .find(GuildId::new(1), &first.to_string(), None)
+                .map(|s| s.metadata.id)
+                .ok(),
+            Some(first)
+        );
+        assert!(matches!(
+            state.find(GuildId::new(1), &second.to_string(), None),
+            Err(SoundboardError::SoundNotFound)
+        ));
+
+        state.remove(first);
+        assert!(matches!(
+            state.find(GuildId::new(1), "hello", None),
+            Err(SoundboardError::SoundNotFound)
+        ));
+        assert!(state.contains(GuildId::new(2), second));
+    }
+
+    #[test]
+    fn shared_files() {
+        let mut state = State::default();
+        let first = add(&mut state, 1, "a", "g", None);
+        let second = add(&mut state, 2, "b", "g", None);
+        let hash = Some([1; 32]);
+        for id in [first, second] {
+            let metadata = &mut state.sounds.get_mut(&id).unwrap().metadata;
+            metadata.hash = hash;
+        }
+        let sound = state.remove(second).unwrap();
+        state.insert(Sound::new(
+            SoundMetadata {
+                file: first,
+                ..sound.metadata
+            },
+            0,
+            Duration::ZERO,
+        ));
+
+        assert_eq!(state.file_with_hash([1; 32]), Some(first));
+        assert!(state.guild_duplicate(GuildId::new(2), [1; 32]).is_some());
+        assert!(state.guild_duplicate(GuildId::new(3), [1; 32]).is_none());
+        assert_eq!(state.files.get(&first), Some(&2));
+        assert!(!state.files.contains_key(&second));
+
+        state.remove(first);
+        assert_eq!(state.files.get(&first), Some(&1));
+        state.remove(second);
+        assert!(state.files.is_empty());
+    }
+
+    #[test]
+    fn insert_position() {
+        let mut state = State::default();
+        let first = add(&mut state, 1, "a", "g", None);
+        let second = add(&mut state, 1, "b", "g", None);
+        let inserted = add(&mut state, 1, "c", "g", Some(0));
+
+        let order = |state: &State| {
+            let mut sounds = state
+                .guild_sounds(GuildId::new(1))
+                .map(|s| (s.metadata.index, s.metadata.id))
+                .collect::<Vec<_>>();
+            sounds.sort();
+            sounds.into_iter().map(|(_, id)| id).collect::<Vec<_>>()
+        };
+        assert_eq!(order(&state), vec![inserted, first, second]);
+        assert!(matches!(
+            state.prepare_insert(
+                GuildId::new(1),
+                Ulid::new(),
+                "A".to_owned(),
+                None,
+                ButtonStyle::Primary,
+                "G".to_owned(),
+                None,
+            ),
+            Err(SoundboardError::NameTaken)
+        ));
+    }
+
+    #[test]
+    fn reposition() {
+        let mut state = State::default();
+        let first = add(&mut state, 1, "a", "g", None);
+        let second = add(&mut state, 1, "b", "g", None);
+        let third = add(&mut state, 1, "c", "g", None);
+        let other = add(&mut state, 1, "d", "h", None);
+
+        let order = |state: &State| {
+            state
+                .guild_sounds(GuildId::new(1))
+                .filter(|s| s.metadata.group == "g")
+                .map(|s| (s.metadata.index, s.metadata.id))
+                .sorted()
+                .map(|(_, id)| id)
+                .collect::<Vec<_>>()
+        };
+        assert!(state.reposition(GuildId::new(1), third, 0));
+        assert_eq!(order(&state), vec![third, first, second]);
+        assert!(state.reposition(GuildId::new(1), third, 9));
+        assert_eq!(order(&state), vec![first, second, third]);
+
+        // Same position or alone in its group.
+        assert!(!state.reposition(GuildId::new(1), second, 1));
+        assert!(!state.reposition(GuildId::new(1), other, 3));
+    }
+
+    #[test]
+    fn move_to_group() {
+        let mut state = State::default();
+        let moved = add(&mut state, 1, "a", "g", None);
+        add(&mut state, 1, "b", "g", None);
+        let first = add(&mut state, 1, "c", "h", None);
+        state.sounds.get_mut(&first).unwrap().metadata.index = 5 * INDEX_GAP;
+
+        // Appended after the sounds of its new group, whatever its old index.
+        let update = SoundUpdate {
+            group: Some("H".to_owned()),
+            ..SoundUpdate::default()
+        };
+        assert!(state.apply_update(GuildId::new(1), moved, update).unwrap());
+        assert_eq!(state.sounds[&moved].metadata.group, "h");
+        assert_eq!(state.sounds[&moved].metadata.index, 6 * INDEX_GAP);
+
+        // Unless placed explicitly.
+        let other = add(&mut state, 1, "d", "g", None);
+        let update = SoundUpdate {
+            group: Some("h".to_owned()),
+            position: Some(0),
+            ..SoundUpdate::default()
+        };
+        assert!(state.apply_update(GuildId::new(1), other, update).unwrap());
+        assert_eq!(
+            state
+                .group_sounds(GuildId::new(1), "h")
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect_vec(),
+            [other, first, moved]
+        );
+    }
+
+    #[test]
+    fn sparse_indexes() {
+        let mut state = State::default();
+        let first = add(&mut state, 1, "a", "g", None);
+        let second = add(&mut state, 1, "b", "g", None);
+
+        // Inserting between two sounds leaves the others untouched.
+        let (index, renumbered) = state.index_at(GuildId::new(1), "g", Some(1), None);
+        assert!(!renumbered);
+        assert!(state.sounds[&first].metadata.index < index);
+        assert!(index < state.sounds[&second].metadata.index);
+
+        // Until there is no room left, like with the indexes of older versions.
+        state.sounds.get_mut(&first).unwrap().metadata.index = 0;
+        state.sounds.get_mut(&second).unwrap().metadata.index = 1;
+        let (index, renumbered) = state.index_at(GuildId::new(1), "g", Some(1), None);
+        assert!(renumbered);
+        assert_eq!(
+            (
+                state.sounds[&first].metadata.index,
+                index,
+                state.sounds[&second].metadata.index
+            ),
+            (INDEX_GAP, 2 * INDEX_GAP, 3 * INDEX_GAP)
+        );
+    }
+
+    #[test]
+    fn group_order() {
+        let mut state = State::default();
+        for group in ["c", "b", "a"] {
+            add(&mut state, 1, "sound", group, None);
+        }
+        assert_eq!(state.guild_groups(GuildId::new(1)), ["a", "b", "c"]);
+
+        // Ordered groups come first, even when missing or no longer used.
+        state.groups.insert(
+            GuildId::new(1),
+            vec![
+                GroupMetadata {
+                    emoji: Some("🐸".to_owned()),
+                    ..GroupMetadata::new("c".to_owned())
+                },
+                GroupMetadata::new("gone".to_owned()),
+            ],
+        );
+        assert_eq!(state.guild_groups(GuildId::new(1)), ["c", "a", "b"]);
+        assert_eq!(state.find_group(GuildId::new(1), "C").unwrap(), "c");
+
+        // Materializing the order keeps the settings of used groups only.
+        let groups = state.ordered_groups(GuildId::new(1));
+        assert_eq!(
+            groups.iter().map(|g| g.name.as_str()).collect_vec(),
+            ["c", "a", "b"]
+        );
+        assert_eq!(groups[0].emoji.as_deref(), Some("🐸"));
+
+        // Backups follow the display order too.
+        assert_eq!(
+            state
+                .backup_groups(GuildId::new(1))
+                .iter()
+                .map(|g| g.group.as_str())
+                .collect_vec(),
+            ["c", "a", "b"]
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! On-disk format of the soundboard, shared with the `disrecord-admin` binary.
+
+use std::path::{Path, PathBuf};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serenity::all::ButtonStyle;
+use thiserror::Error as ThisError;
+use ulid::Ulid;
+
+/// Metadata files start with this magic and the version of their format,
+/// followed by a stream of records. Files without it predate the versioning
+/// and hold `SoundMetadataV1` records.
+const METADATA_MAGIC: &[u8; 8] = b"DRSNDBRD";
+pub const METADATA_VERSION: u16 = 2;
+
+/// Gap between the indexes of consecutive sounds of a group, leaving room to
+/// insert sounds between them without moving the others.
+pub const INDEX_GAP: usize = 1 << 20;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SoundMetadata {
+    pub guild: u64,
+    pub id: Ulid,
+    /// Identical sounds share the file of the first one uploaded.
+    pub file: Ulid,
+    /// SHA-256 of the PCM payload, unknown if the file was missing during the
+    /// migration from the legacy format.
+    pub hash: Option<[u8; 32]>,
+    pub name: String,
+    pub emoji: Option<String>,
+    pub color: ButtonStyle,
+    pub group: String,
+    pub index: usize,
+}
+
+/// Metadata record of the unversioned format, where every sound had its own
+/// file named after it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SoundMetadataV1 {
+    pub guild: u64,
+    pub id: Ulid,
+    pub name: String,
+    pub emoji: Option<String>,
+    pub color: ButtonStyle,
+    pub group: String,
+    pub index: usize,
+}
+
+impl SoundMetadataV1 {
+    pub fn upgrade(self, hash: Option<[u8; 32]>) -> SoundMetadata {
+        SoundMetadata {
+            guild: self.guild,
+            id: self.id,
+            file: self.id,
+            hash,
+            name: self.name,
+            emoji: self.emoji,
+            color: self.color,
+            group: self.group,
+            index: self.index,
+        }
+    }
+}
+
+impl From<SoundMetadata> for SoundMetadataV1 {
+    /// The sound must have a file of its own, the legacy format has no way to
+    /// share one.
+    fn from(metadata: SoundMetadata) -> Self {
+        Self {
+            guild: metadata.guild,
+            id: metadata.id,
+            name: metadata.name,
+            emoji: metadata.emoji,
+            color: metadata.color,
+            group: metadata.group,
+            index: metadata.index,
+        }
+    }
+}
+
+/// Group of a guild, stored apart from the sounds in a JSON file listing the
+/// groups of each guild in display order.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GroupMetadata {
+    pub name: String,
+    /// Color of the sounds uploaded to the group without one.
+    #[serde(default)]
+    pub color: Option<ButtonStyle>,
+    /// Shown before the name in the group header.
+    #[serde(default)]
+    pub emoji: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl GroupMetadata {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            color: None,
+            emoji: None,
+            description: None,
+        }
+    }
+
+    /// Markdown header of the group in sound lists.
+    pub fn header(&self) -> String {
+        let mut header = match &self.emoji {
+            Some(emoji) => format!("# {emoji} {}", self.name),
+            None => format!("# {}", self.name),
+        };
+        if let Some(description) = &self.description {
+            header.push('\n');
+            header.push_str(description);
+        }
+        header
+    }
+}
+
+/// Content of a metadata file, in the format version it was written with.
+#[derive(Debug)]
+pub enum Metadata {
+    V1(Vec<SoundMetadataV1>),
+    V2(Vec<SoundMetadata>),
+}
+
+impl Metadata {
+    #[allow(dead_code)]
+    pub fn version(&self) -> u16 {
+        match self {
+            Metadata::V1(_) => 1,
+            Metadata::V2(_) => 2,
+        }
+    }
+}
+
+pub fn decode_metadata(file: &[u8]) -> Result<Metadata, StoreError> {
+    let Some(file) = file.strip_prefix(METADATA_MAGIC) else {
+        return Ok(Metadata::V1(read_records(file)));
+    };
+    let (version, records) = file
+        .split_first_chunk()
+        .ok_or(StoreError::TruncatedHeader)?;
+    match u16::from_le_bytes(*version) {
+        2 => Ok(Metadata::V2(read_records(records))),
+        version => Err(StoreError::UnsupportedVersion(version)),
+    }
+}
+
+/// Encode a whole metadata file in the current format.
+pub fn encode_metadata<'a>(
+    sounds: impl IntoIterator<Item = &'a SoundMetadata>,
+) -> Result<Vec<u8>, StoreError> {
+    let mut data = metadata_header();
+    for sound in sounds {
+        data.extend(encode_record(sound)?);
+    }
+    Ok(data)
+}
+
+/// Encode a whole metadata file in the legacy unversioned format.
+#[allow(dead_code)]
+pub fn encode_metadata_v1<'a>(
+    sounds: impl IntoIterator<Item = &'a SoundMetadataV1>,
+) -> Result<Vec<u8>, StoreError> {
+    let mut data = Vec::new();
+    for sound in sounds {
+        data.extend(bincode::serialize(sound)?);
+    }
+    Ok(data)
+}
+
+/// Header to write before the first record appended to an empty file.
+pub fn metadata_header() -> Vec<u8> {
+    let mut header = METADATA_MAGIC.to_vec();
+    header.extend_from_slice(&METADATA_VERSION.to_le_bytes());
+    header
+}
+
+pub fn encode_record(sound: &SoundMetadata) -> Result<Vec<u8>, StoreError> {
+    Ok(bincode::serialize(sound)?)
+}
+
+/// Read a stream of records, stopping at the first invalid one.
+fn read_records<T: DeserializeOwned>(data: &[u8]) -> Vec<T> {
+    let mut deserializer = bincode::Deserializer::from_slice(
+        data,
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes(),
+    );
+    let mut records = Vec::new();
+    while let Ok(record) = T::deserialize(&mut deserializer) {
+        records.push(record);
+    }
+    records
+}
+
+pub fn file_path(dir_path: &Path, id: Ulid) -> PathBuf {
+    let mut path = dir_path.join(id.to_string());
+    path.set_extension("wav");
+    path
+}
+
+/// Name of the metadata entry of backup archives, next to one `<id>.wav` file
+/// per sound.
+pub const BACKUP_METADATA_NAME: &str = "sounds.json";
+
+/// Groups of a backup, sorted by name, with their sounds sorted by position.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupGroup {
+    pub group: String,
+    pub sounds: Vec<BackupSound>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupSound {
+    pub id: Ulid,
+    pub name: String,
+    pub emoji: Option<String>,
+    pub color: String,
+}
+
+pub fn backup_file_name(id: Ulid) -> String {
+    format!("{id}.wav")
+}
+
+#[derive(ThisError, Debug)]
+pub enum StoreError {
+    #[error("Metadata file header is truncated.")]
+    TruncatedHeader,
+    #[error("Unsupported metadata format version {0}.")]
+    UnsupportedVersion(u16),
+    #[error("Failed to encode metadata: {0}")]
+    Encoding(#[from] bincode::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::all::ButtonStyle;
+    use ulid::Ulid;
+
+    use super::{Metadata, SoundMetadataV1, StoreError};
+
+    fn legacy(name: &str) -> SoundMetadataV1 {
+        SoundMetadataV1 {
+            guild: 1,
+            id: Ulid::new(),
+            name: name.to_owned(),
+            emoji: None,
+            color: ButtonStyle::Success,
+            group: "g".to_owned(),
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn legacy_round_trip() {
+        let sounds = vec![legacy("a"), legacy("b")];
+        let data = super::encode_metadata_v1(&sounds).unwrap();
+        let Ok(Metadata::V1(decoded)) = super::decode_metadata(&data) else {
+            panic!("Legacy file not detected");
+        };
+        assert_eq!(decoded.len(), 2);
+
+        let upgraded = decoded
+            .into_iter()
+            .map(|s| s.upgrade(None))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            (upgraded[0].id, upgraded[0].file),
+            (sounds[0].id, sounds[0].id)
+        );
+        let data = super::encode_metadata(&upgraded).unwrap();
+        let Ok(Metadata::V2(decoded)) = super::decode_metadata(&data) else {
+            panic!("Current file not detected");
+        };
+        assert_eq!(decoded[1].name, "b");
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut data = super::metadata_header();
+        data[8] = 42;
+        assert!(matches!(
+            super::decode_metadata(&data),
+            Err(StoreError::UnsupportedVersion(42))
+        ));
+        assert!(matches!(
+            super::decode_metadata(&data[..9]),
+            Err(StoreError::TruncatedHeader)
+        ));
+    }
+}
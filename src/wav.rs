@@ -1,5 +1,9 @@
 use std::time::Duration;
 
+use sha2::{Digest, Sha256};
+
+/// Sample rate of every sound, the one of Discord voice.
+pub const FREQUENCY: usize = 48_000;
 pub const HEADER_SIZE: usize = 44;
 
 const HEADER_TEMPLATES: [&[u8]; 2] = [
@@ -44,15 +48,85 @@ pub fn remove_header(wav: &mut Vec<u8>) {
     wav.truncate(wav.len() - HEADER_SIZE);
 }
 
-/// Validates that the data are a valid WAV containing PCM i16 LE data.
+/// Validates that the data are a WAV containing only mono PCM i16 LE data at
+/// the frequency of Discord voice, behind the header written by [`package`].
+#[allow(dead_code)]
 pub fn is_valid_pcm_s16le(data: &[u8]) -> bool {
-    if data.len() < HEADER_SIZE || data.len() - HEADER_SIZE % 2 == 1 {
+    data.len() >= HEADER_SIZE && has_valid_header(&data[..HEADER_SIZE], data.len())
+}
+
+/// Validates the header of a WAV of `len` bytes, like [`is_valid_pcm_s16le`]
+/// without reading the whole file.
+pub fn has_valid_header(header: &[u8], len: usize) -> bool {
+    if header.len() < HEADER_SIZE || len < HEADER_SIZE || (len - HEADER_SIZE) % 2 == 1 {
         return false;
     }
-    &data[0..4] == HEADER_TEMPLATES[0]
-        && data[4..8] == ((data.len() - 8) as u32).to_le_bytes()
-        && &data[8..40] == HEADER_TEMPLATES[1]
-        && data[40..44] == ((data.len() - HEADER_SIZE) as u32).to_le_bytes()
+    &header[0..4] == HEADER_TEMPLATES[0]
+        && header[4..8] == ((len - 8) as u32).to_le_bytes()
+        && &header[8..40] == HEADER_TEMPLATES[1]
+        && header[40..44] == ((len - HEADER_SIZE) as u32).to_le_bytes()
+}
+
+/// Find the PCM payload of a WAV, skipping chunks other than `fmt ` and
+/// `data`. Only mono PCM i16 LE at the frequency of Discord voice is accepted.
+pub fn pcm_data(wav: &[u8]) -> Option<&[u8]> {
+    if wav.get(0..4)? != b"RIFF" || wav.get(8..12)? != b"WAVE" {
+        return None;
+    }
+
+    let mut chunks = &wav[12..];
+    let mut valid_format = false;
+    while chunks.len() >= 8 {
+        let size = u32::from_le_bytes(chunks[4..8].try_into().ok()?) as usize;
+        let body = &chunks[8..];
+        match &chunks[0..4] {
+            b"fmt " => {
+                let format = body.get(..16)?;
+                valid_format = format[0..2] == 1u16.to_le_bytes() // PCM
+                    && format[2..4] == 1u16.to_le_bytes() // Channels
+                    && format[4..8] == (FREQUENCY as u32).to_le_bytes()
+                    && format[14..16] == 16u16.to_le_bytes(); // Bits per sample
+            }
+            b"data" if valid_format => {
+                // Streamed WAVs, like the output of ffmpeg to a pipe, don't
+                // know the size of their data.
+                let data = &body[..size.min(body.len())];
+                return Some(&data[..data.len() / 2 * 2]);
+            }
+            b"data" => return None,
+            _ => {}
+        }
+        // Chunks are padded to an even size.
+        chunks = body.get(size.checked_add(size % 2)?..)?;
+    }
+    None
+}
+
+/// Rewrite a WAV with the header written by [`package`], dropping any other
+/// chunk. Returns `None` if its data are not in the expected format.
+pub fn normalize(wav: &[u8]) -> Option<Vec<u8>> {
+    let pcm = pcm_data(wav)?;
+    let mut data = Vec::with_capacity(HEADER_SIZE + pcm.len());
+    write_header(&mut data, pcm.len());
+    data.extend_from_slice(pcm);
+    Some(data)
+}
+
+/// Convert the i16 LE PCM payload of a WAV into raw f32 LE samples, ready to be
+/// played without any further decoding. WAVs in another format decode to
+/// silence rather than noise.
+pub fn decode_f32(wav: &[u8]) -> Vec<u8> {
+    pcm_data(wav)
+        .unwrap_or_default()
+        .chunks_exact(2)
+        .flat_map(|n| (i16::from_le_bytes([n[0], n[1]]) as f32 / -(i16::MIN as f32)).to_le_bytes())
+        .collect()
+}
+
+/// Hash the PCM payload of a WAV, so identical sounds are detected whatever
+/// their original file and metadata. WAVs in another format are hashed whole.
+pub fn pcm_hash(wav: &[u8]) -> [u8; 32] {
+    Sha256::digest(pcm_data(wav).unwrap_or(wav)).into()
 }
 
 pub fn duration_from_size(size: usize) -> Duration {
@@ -60,7 +134,12 @@ pub fn duration_from_size(size: usize) -> Duration {
         return Duration::from_secs(0);
     }
     // Multiply by 1_000 and use milliseconds to gain in precision.
-    Duration::from_millis(((size - HEADER_SIZE) / 2 * 1_000 / crate::recorder::FREQUENCY) as u64)
+    Duration::from_millis(((size - HEADER_SIZE) / 2 * 1_000 / FREQUENCY) as u64)
+}
+
+/// Size of a WAV lasting `duration`, the inverse of `duration_from_size`.
+pub fn size_from_duration(duration: Duration) -> usize {
+    HEADER_SIZE + (duration.as_millis() as usize * FREQUENCY / 1_000) * 2
 }
 
 #[cfg(test)]
@@ -68,17 +147,17 @@ mod tests {
     use std::{iter, time::Duration};
 
     use itertools::Itertools;
-    use rand::random;
+    use rand::{random, random_range};
 
     const BITS_PER_SAMPLE: u32 = i16::BITS;
     const CHANNELS: u16 = 1;
 
-    use super::HEADER_SIZE;
+    use super::{FREQUENCY, HEADER_SIZE};
 
     #[test]
     fn package() {
-        let pcm = iter::repeat_with(|| random::<i16>())
-            .take(64 + random::<usize>() % 64)
+        let pcm = iter::repeat_with(random::<i16>)
+            .take(64 + random_range(0..64))
             .collect_vec();
 
         // http://soundfile.sapp.org/doc/WaveFormat
@@ -90,10 +169,9 @@ mod tests {
         data.extend_from_slice(&(16u32.to_le_bytes())); // Size of sub-chunk
         data.extend_from_slice(&(1u16.to_le_bytes())); // PCM format
         data.extend_from_slice(&(CHANNELS.to_le_bytes()));
-        data.extend_from_slice(&(crate::recorder::FREQUENCY as u32).to_le_bytes());
+        data.extend_from_slice(&(FREQUENCY as u32).to_le_bytes());
         data.extend_from_slice(
-            &(crate::recorder::FREQUENCY as u32 * CHANNELS as u32 * BITS_PER_SAMPLE / 8)
-                .to_le_bytes(),
+            &(FREQUENCY as u32 * CHANNELS as u32 * BITS_PER_SAMPLE / 8).to_le_bytes(),
         );
         data.extend_from_slice(&(CHANNELS * BITS_PER_SAMPLE as u16 / 8).to_le_bytes());
         data.extend_from_slice(&(BITS_PER_SAMPLE as u16).to_le_bytes());
@@ -108,7 +186,7 @@ mod tests {
 
     #[test]
     fn package_mut_raw() {
-        let pcm = iter::repeat_with(|| random::<i16>()).take(2).collect_vec();
+        let pcm = iter::repeat_with(random::<i16>).take(2).collect_vec();
         assert_eq!(super::package(&pcm), {
             let mut data = pcm.iter().flat_map(|n| n.to_le_bytes()).collect();
             super::package_mut_raw(&mut data);
@@ -118,8 +196,8 @@ mod tests {
 
     #[test]
     fn remove_header() {
-        let pcm = iter::repeat_with(|| random::<i16>())
-            .take(64 + random::<usize>() % 64)
+        let pcm = iter::repeat_with(random::<i16>)
+            .take(64 + random_range(0..64))
             .collect_vec();
         let mut wav = super::package(&pcm);
 
@@ -128,6 +206,78 @@ mod tests {
         assert_eq!(pcm, wav);
     }
 
+    #[test]
+    fn decode_f32() {
+        let decoded = super::decode_f32(&super::package(&[0, i16::MIN, i16::MAX / 2]));
+        let samples = decoded
+            .chunks_exact(4)
+            .map(|n| f32::from_le_bytes(n.try_into().unwrap()))
+            .collect_vec();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0], 0.);
+        assert_eq!(samples[1], -1.);
+        assert!((samples[2] - 0.5).abs() < 0.001);
+    }
+
+    /// Build a WAV the way ffmpeg streams it, with a metadata chunk and unknown
+    /// sizes.
+    fn streamed_wav(channels: u16, frequency: u32, pcm: &[i16]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(b"WAVE");
+        data.extend_from_slice(b"fmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&channels.to_le_bytes());
+        data.extend_from_slice(&frequency.to_le_bytes());
+        data.extend_from_slice(&(frequency * channels as u32 * 2).to_le_bytes());
+        data.extend_from_slice(&(channels * 2).to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"LIST");
+        data.extend_from_slice(&5u32.to_le_bytes());
+        data.extend_from_slice(b"INFO\0\0"); // Padded to an even size.
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend(pcm.iter().flat_map(|n| n.to_le_bytes()));
+        data
+    }
+
+    #[test]
+    fn normalize() {
+        let pcm = [0, i16::MIN, i16::MAX];
+        let wav = streamed_wav(CHANNELS, FREQUENCY as u32, &pcm);
+        assert!(!super::is_valid_pcm_s16le(&wav));
+        assert_eq!(super::normalize(&wav), Some(super::package(&pcm)));
+        assert_eq!(
+            super::decode_f32(&wav),
+            super::decode_f32(&super::package(&pcm))
+        );
+
+        // Other formats must be transcoded, and are never played as is.
+        for wav in [
+            streamed_wav(2, FREQUENCY as u32, &pcm),
+            streamed_wav(CHANNELS, 44_100, &pcm),
+        ] {
+            assert_eq!(super::pcm_data(&wav), None);
+            assert_eq!(super::normalize(&wav), None);
+            assert!(super::decode_f32(&wav).is_empty());
+        }
+    }
+
+    #[test]
+    fn pcm_hash() {
+        let pcm = [1, 2, 3];
+        assert_eq!(
+            super::pcm_hash(&streamed_wav(CHANNELS, FREQUENCY as u32, &pcm)),
+            super::pcm_hash(&super::package(&pcm))
+        );
+        assert_ne!(
+            super::pcm_hash(&super::package(&pcm)),
+            super::pcm_hash(&super::package(&[1, 2, 4]))
+        );
+    }
+
     #[test]
     fn validate() {
         assert!(super::is_valid_pcm_s16le(include_bytes!("hello.wav")));